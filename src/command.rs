@@ -0,0 +1,311 @@
+//! Completion of commands, subcommands, and flags.
+//!
+//! Describe the commands with [`Command`] and [`Flag`], then use the root
+//! command as the editor's [`Completion`]. It works out where in the
+//! grammar the cursor sits, and offers the matching subcommands, flags, or
+//! argument values.
+//!
+//! The input is split into words as described in [`words`], on the
+//! default [`COMPLETION_BREAKS`]. If the editor breaks words on other
+//! characters too, such as the `=` of `--host=localhost`, the candidates
+//! are trimmed to the part of the word it replaces.
+//!
+//! Example:
+//! ```
+//! use linoleum::command::{Command, Flag};
+//! use linoleum::{path, Candidate, Completion, Editor};
+//!
+//! fn hosts(_data: &str, _start: usize, _end: usize) -> Vec<Candidate> {
+//!     vec!["localhost".into(), "example.com".into()]
+//! }
+//!
+//! let commands = Command::root()
+//!     .subcommand(
+//!         Command::new("db")
+//!             .subcommand(
+//!                 Command::new("connect")
+//!                     .flag(Flag::new("--host").value(hosts))
+//!                     .flag(Flag::new("--port").value(|_: &str, _: usize, _: usize| {
+//!                         Vec::<Candidate>::new()
+//!                     }))
+//!                     .flag(Flag::new("--verbose").alias("-v")),
+//!             )
+//!             .subcommand(Command::new("import").arg(path::complete)),
+//!     )
+//!     .subcommand(Command::new("exit"));
+//!
+//! let found = commands.complete("db c", 3, 4);
+//! assert_eq!(found[0].text, "connect");
+//!
+//! let found = commands.complete("db connect --host ", 18, 18);
+//! assert_eq!(found[0].text, "localhost");
+//!
+//! let found = commands.complete("db connect --host=lo", 11, 20);
+//! assert_eq!(found[0].text, "--host=localhost");
+//!
+//! // With an editor breaking words on `=` too.
+//! let found = commands.complete("db connect --host=lo", 18, 20);
+//! assert_eq!(found[0].text, "localhost");
+//!
+//! let editor = Editor::new(" > ").completion(commands);
+//! ```
+
+use std::fmt;
+use std::ptr;
+use std::sync::Arc;
+
+use crate::matching::Matcher;
+use crate::{words, Candidate, Completion, COMPLETION_BREAKS};
+
+/// A command, with its subcommands, flags, and positional arguments.
+///
+/// The root of the tree is created with [`Command::root`].
+#[derive(Clone, Default)]
+pub struct Command {
+    name: String,
+    subcommands: Vec<Command>,
+    flags: Vec<Flag>,
+    args: Vec<Arc<dyn Completion + Send + Sync>>,
+    matcher: Matcher,
+}
+
+impl Command {
+    /// Creates a new command with the given name.
+    pub fn new<S: ToString>(name: S) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates the root of a command tree, which has no name.
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Adds a subcommand.
+    ///
+    /// Subcommands are only offered before any positional arguments.
+    pub fn subcommand(mut self, subcommand: Command) -> Self {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    /// Adds a flag.
+    pub fn flag(mut self, flag: Flag) -> Self {
+        self.flags.push(flag);
+        self
+    }
+
+    /// Adds a positional argument, whose values are completed by
+    /// `completion`.
+    ///
+    /// Arguments are completed in the order they were added.
+    pub fn arg<C: Completion + Send + Sync + 'static>(mut self, completion: C) -> Self {
+        self.args.push(Arc::new(completion));
+        self
+    }
+
+    /// Sets how the subcommands and flags of this command are matched
+    /// against the input. Defaults to [`Matcher::Prefix`].
+    pub fn matcher(self, matcher: Matcher) -> Self {
+        Self { matcher, ..self }
+    }
+
+    /// Finds a flag by any of its names.
+    fn find_flag(&self, name: &str) -> Option<&Flag> {
+        self.flags
+            .iter()
+            .find(|flag| flag.names.iter().any(|n| n == name))
+    }
+
+    /// Completes the word found on the default [`COMPLETION_BREAKS`],
+    /// whose contents are `start..end`.
+    fn complete_word(&self, data: &str, start: usize, end: usize) -> Vec<Candidate> {
+        let word = words::word_at(data, end, COMPLETION_BREAKS);
+        let before: String = data.chars().take(word.start).collect();
+        let word = word.text;
+
+        let mut command = self;
+        let mut expecting: Option<&Flag> = None;
+        let mut used: Vec<&Flag> = Vec::new();
+        let mut position = 0;
+        let mut flags_done = false;
+
+        for token in words::split(&before, COMPLETION_BREAKS) {
+            if expecting.take().is_some() {
+                continue;
+            }
+
+            let token = token.as_str();
+            if !flags_done && token == "--" {
+                flags_done = true;
+            } else if !flags_done && token.starts_with('-') {
+                let (name, value) = match token.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (token, None),
+                };
+
+                if let Some(flag) = command.find_flag(name) {
+                    used.push(flag);
+                    if flag.value.is_some() && value.is_none() {
+                        expecting = Some(flag);
+                    }
+                }
+            } else if let Some(sub) = command
+                .subcommands
+                .iter()
+                .find(|sub| position == 0 && sub.name == token)
+            {
+                command = sub;
+                used.clear();
+            } else {
+                position += 1;
+            }
+        }
+
+        if let Some(value) = expecting.and_then(|flag| flag.value.as_ref()) {
+            return value.complete(data, start, end);
+        }
+
+        if !flags_done && word.starts_with('-') {
+            if let Some((name, _)) = word.split_once('=') {
+                let Some(value) = command.find_flag(name).and_then(|f| f.value.as_ref()) else {
+                    return Vec::new();
+                };
+
+                // Complete the value, keeping the flag in front of it.
+                let offset = name.chars().count() + 1;
+                return value
+                    .complete(data, start + offset, end)
+                    .into_iter()
+                    .map(|c| Candidate {
+                        text: format!("{name}={}", c.text),
+                        positions: c.positions.iter().map(|p| p + offset).collect(),
+                        ..c
+                    })
+                    .collect();
+            }
+
+            let names = command
+                .flags
+                .iter()
+                .filter(|flag| !used.iter().any(|u| ptr::eq(*u, *flag)))
+                .flat_map(|flag| flag.names.iter().cloned());
+
+            return command.matcher.filter(&word, names);
+        }
+
+        let mut found = Vec::new();
+        if position == 0 {
+            let names = command.subcommands.iter().map(|sub| sub.name.clone());
+            found = command.matcher.filter(&word, names);
+        }
+
+        if let Some(arg) = command.args.get(position) {
+            found.extend(arg.complete(data, start, end));
+        }
+
+        found
+    }
+}
+
+impl Completion for Command {
+    fn complete(&self, data: &str, start: usize, end: usize) -> Vec<Candidate> {
+        let word = words::word_at(data, end, COMPLETION_BREAKS);
+        let found = self.complete_word(data, word.content_start(), end);
+
+        let replaced = words::word_in(data, start, end);
+        rebase(found, &word.text, &replaced)
+    }
+}
+
+/// Turns candidates for `word` into ones for `replaced`, the word the
+/// editor replaces, when it breaks words on different characters: their
+/// common part is removed from or added to the front of the candidates.
+fn rebase(found: Vec<Candidate>, word: &str, replaced: &str) -> Vec<Candidate> {
+    if let Some(prefix) = word.strip_suffix(replaced) {
+        let skipped = prefix.chars().count();
+        found
+            .into_iter()
+            .filter_map(|c| {
+                Some(Candidate {
+                    text: c.text.strip_prefix(prefix)?.to_string(),
+                    positions: c
+                        .positions
+                        .iter()
+                        .filter_map(|p| p.checked_sub(skipped))
+                        .collect(),
+                    ..c
+                })
+            })
+            .collect()
+    } else if let Some(prefix) = replaced.strip_suffix(word) {
+        let added = prefix.chars().count();
+        found
+            .into_iter()
+            .map(|c| Candidate {
+                text: format!("{prefix}{}", c.text),
+                positions: c.positions.iter().map(|p| p + added).collect(),
+                ..c
+            })
+            .collect()
+    } else {
+        found
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Command")
+            .field("name", &self.name)
+            .field("subcommands", &self.subcommands)
+            .field("flags", &self.flags)
+            .field("args", &self.args.len())
+            .field("matcher", &self.matcher)
+            .finish()
+    }
+}
+
+/// A flag of a [`Command`], either a switch or one taking a value.
+///
+/// Flags taking a value accept it either as the next word or after an
+/// `=`, as in `--host=localhost`.
+#[derive(Clone)]
+pub struct Flag {
+    names: Vec<String>,
+    value: Option<Arc<dyn Completion + Send + Sync>>,
+}
+
+impl Flag {
+    /// Creates a new switch with the given name, including its dashes.
+    pub fn new<S: ToString>(name: S) -> Self {
+        Self {
+            names: vec![name.to_string()],
+            value: None,
+        }
+    }
+
+    /// Adds another name for the flag, such as a short version.
+    pub fn alias<S: ToString>(mut self, name: S) -> Self {
+        self.names.push(name.to_string());
+        self
+    }
+
+    /// Makes the flag take a value, which is completed by `completion`.
+    pub fn value<C: Completion + Send + Sync + 'static>(self, completion: C) -> Self {
+        Self {
+            value: Some(Arc::new(completion)),
+            ..self
+        }
+    }
+}
+
+impl fmt::Debug for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Flag")
+            .field("names", &self.names)
+            .field("value", &self.value.is_some())
+            .finish()
+    }
+}
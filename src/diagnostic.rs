@@ -0,0 +1,72 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use crate::State;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A problem with part of the user input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The byte range of the input the problem is with.
+    pub range: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a new diagnostic for the byte range `range`.
+    pub fn new<S: ToString>(range: Range<usize>, severity: Severity, message: S) -> Self {
+        Self {
+            range,
+            severity,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A live check of the user input.
+///
+/// Returns the problems with the input, given the state of the editor.
+/// Their ranges are underlined in the input, and the message of the most
+/// severe one is shown below it, preferring those at the cursor. The
+/// input is checked again whenever it changes.
+///
+/// Implemented for any function taking just the current user input.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Diagnostic, Editor, Severity};
+/// fn validate(data: &str) -> Vec<Diagnostic> {
+///     data.match_indices("TODO")
+///         .map(|(i, m)| Diagnostic::new(i..i + m.len(), Severity::Warning, "unfinished"))
+///         .collect()
+/// }
+///
+/// let editor = Editor::new(" > ").validator(validate);
+/// ```
+pub trait Validator {
+    fn validate(&self, state: &State) -> Vec<Diagnostic>;
+}
+
+impl<F: Fn(&str) -> Vec<Diagnostic>> Validator for F {
+    fn validate(&self, state: &State) -> Vec<Diagnostic> {
+        self(state.data)
+    }
+}
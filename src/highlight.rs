@@ -0,0 +1,302 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::render::{Cell, Line};
+use crate::{Color, State, Style};
+
+/// A style applied to part of the user input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The byte range of the input to style.
+    pub range: Range<usize>,
+    pub style: Style,
+}
+
+impl Span {
+    /// Creates a new span styling the byte range `range`.
+    pub fn new(range: Range<usize>, style: Style) -> Self {
+        Self { range, style }
+    }
+}
+
+/// A highlighting scheme to apply to the user input.
+///
+/// Returns the spans to style, given the state of the editor. Spans may
+/// overlap, in which case later ones are drawn over earlier ones; see
+/// [`Style::patch`]. The text itself is always drawn as-is.
+///
+/// Implemented for any function taking just the current user input. The
+/// input is highlighted again whenever it changes or the cursor moves.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Editor, Highlighter, Span, State, Style};
+/// // Underlines the word under the cursor.
+/// struct CurrentWord;
+///
+/// impl Highlighter for CurrentWord {
+///     fn highlight(&self, state: &State) -> Vec<Span> {
+///         let is_break = |ch: char| ch.is_whitespace();
+///         let (before, after) = state.data.split_at(state.cursor);
+///         let start = before.rfind(is_break).map_or(0, |i| i + 1);
+///         let end = after.find(is_break).map_or(state.data.len(), |i| state.cursor + i);
+///
+///         vec![Span::new(start..end, Style::default().underline(true))]
+///     }
+/// }
+///
+/// let editor = Editor::new(" > ").highlight(CurrentWord);
+/// ```
+pub trait Highlighter {
+    fn highlight(&self, state: &State) -> Vec<Span>;
+}
+
+impl<F: Fn(&str) -> Vec<Span>> Highlighter for F {
+    fn highlight(&self, state: &State) -> Vec<Span> {
+        self(state.data)
+    }
+}
+
+/// Lays out `data` as cells with the given spans applied.
+pub(crate) fn render(data: &str, spans: &[Span]) -> Line {
+    let mut styles = vec![Style::default(); data.len()];
+    for span in spans {
+        let end = span.range.end.min(data.len());
+        for style in styles.get_mut(span.range.start..end).unwrap_or_default() {
+            *style = style.patch(span.style);
+        }
+    }
+
+    data.char_indices()
+        .map(|(i, ch)| Cell {
+            text: ch.to_string(),
+            style: styles[i],
+            escapes: String::new(),
+        })
+        .collect()
+}
+
+/// A highlighter for brackets and quotes, drawn over another highlighter.
+///
+/// Highlights the bracket paired with the one at or just before the
+/// cursor, and marks brackets and quotes that aren't closed or opened.
+/// Brackets inside quotes are ignored, and a backslash escapes the next
+/// character inside quotes.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Brackets, Color, Editor, Highlighter, Span, State, Style};
+/// fn numbers(data: &str) -> Vec<Span> {
+///     data.char_indices()
+///         .filter(|(_, ch)| ch.is_ascii_digit())
+///         .map(|(i, _)| Span::new(i..i + 1, Style::default().fg(Color::Blue)))
+///         .collect()
+/// }
+///
+/// let editor = Editor::new(" > ").highlight(Brackets::new().highlight(numbers));
+///
+/// // Just after `]`: the `[` and `]` are paired, and the `(` is unmatched.
+/// let mut state = State::default();
+/// state.data = "f(a, [b]";
+/// state.cursor = 8;
+///
+/// let ranges: Vec<_> = Highlighter::highlight(&Brackets::new(), &state)
+///     .into_iter()
+///     .map(|span| span.range)
+///     .collect();
+/// assert_eq!(ranges, [5..6, 7..8, 1..2]);
+/// ```
+#[derive(Clone)]
+pub struct Brackets {
+    highlight: Option<Arc<dyn Highlighter + Send + Sync>>,
+    matching: Style,
+    unmatched: Style,
+}
+
+impl Brackets {
+    /// Creates a new bracket highlighter with the default styles.
+    pub fn new() -> Self {
+        Self {
+            highlight: None,
+            matching: Style::default().fg(Color::Cyan).bold(true),
+            unmatched: Style::default().fg(Color::Red),
+        }
+    }
+
+    /// Sets the highlighter to draw the brackets over.
+    pub fn highlight<H: Highlighter + Send + Sync + 'static>(self, highlight: H) -> Self {
+        Self {
+            highlight: Some(Arc::new(highlight)),
+            ..self
+        }
+    }
+
+    /// Sets the style of the pair of brackets at the cursor.
+    pub fn matching(self, matching: Style) -> Self {
+        Self { matching, ..self }
+    }
+
+    /// Sets the style of brackets and quotes without a pair.
+    pub fn unmatched(self, unmatched: Style) -> Self {
+        Self { unmatched, ..self }
+    }
+}
+
+impl Default for Brackets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Highlighter for Brackets {
+    fn highlight(&self, state: &State) -> Vec<Span> {
+        let mut spans = match &self.highlight {
+            Some(h) => h.highlight(state),
+            None => Vec::new(),
+        };
+
+        let (pairs, unmatched) = pair_brackets(state.data);
+
+        let at = |i: usize| pairs.iter().find(|&&(open, close)| open == i || close == i);
+        let before = state.data[..state.cursor].char_indices().next_back();
+        let pair = at(state.cursor).or_else(|| before.and_then(|(i, _)| at(i)));
+
+        if let Some(&(open, close)) = pair {
+            spans.push(Span::new(open..open + 1, self.matching));
+            spans.push(Span::new(close..close + 1, self.matching));
+        }
+
+        spans.extend(
+            unmatched
+                .into_iter()
+                .map(|i| Span::new(i..i + 1, self.unmatched)),
+        );
+
+        spans
+    }
+}
+
+impl fmt::Debug for Brackets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Brackets")
+            .field("highlight", &self.highlight.is_some())
+            .field("matching", &self.matching)
+            .field("unmatched", &self.unmatched)
+            .finish()
+    }
+}
+
+/// Finds the byte indices of the pairs of brackets in `data`, along with
+/// those of the brackets and quotes without a pair.
+fn pair_brackets(data: &str) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut open: Vec<(usize, char)> = Vec::new();
+    let mut quote: Option<(usize, char)> = None;
+    let mut escaped = false;
+
+    for (i, ch) in data.char_indices() {
+        if let Some((_, q)) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == q {
+                quote = None;
+            }
+
+            continue;
+        }
+
+        match ch {
+            '"' | '\'' => quote = Some((i, ch)),
+            '(' | '[' | '{' => open.push((i, ch)),
+            ')' | ']' | '}' => match open.last() {
+                Some(&(start, o)) if closer(o) == ch => {
+                    open.pop();
+                    pairs.push((start, i));
+                }
+                _ => unmatched.push(i),
+            },
+            _ => {}
+        }
+    }
+
+    unmatched.extend(open.into_iter().map(|(i, _)| i));
+    unmatched.extend(quote.map(|(i, _)| i));
+
+    (pairs, unmatched)
+}
+
+/// The bracket closing `open`.
+fn closer(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_nested_brackets() {
+        let (pairs, unmatched) = pair_brackets("(a[b]{c})");
+        assert_eq!(pairs, [(2, 4), (5, 7), (0, 8)]);
+        assert!(unmatched.is_empty());
+    }
+
+    #[test]
+    fn unmatched_brackets() {
+        assert_eq!(pair_brackets("(]"), (vec![], vec![1, 0]));
+        assert_eq!(pair_brackets("a)"), (vec![], vec![1]));
+        assert_eq!(pair_brackets("[(x)"), (vec![(1, 3)], vec![0]));
+    }
+
+    #[test]
+    fn brackets_in_quotes_are_ignored() {
+        assert_eq!(pair_brackets(r#""(" (x)"#), (vec![(4, 6)], vec![]));
+        assert_eq!(pair_brackets("'[' ]"), (vec![], vec![4]));
+    }
+
+    #[test]
+    fn unmatched_quotes() {
+        assert_eq!(pair_brackets("'a (b"), (vec![], vec![0]));
+
+        // The escaped quote doesn't close the string.
+        assert_eq!(pair_brackets(r#""a\"(" )"#), (vec![], vec![7]));
+    }
+
+    #[test]
+    fn byte_indices() {
+        assert_eq!(pair_brackets("é(x)"), (vec![(2, 4)], vec![]));
+    }
+
+    #[test]
+    fn highlights_pair_at_cursor() {
+        let mut state = State {
+            data: "(a) b [c]",
+            cursor: 0,
+            ..State::default()
+        };
+
+        let ranges = |state: &State| -> Vec<Range<usize>> {
+            Highlighter::highlight(&Brackets::new(), state)
+                .into_iter()
+                .map(|span| span.range)
+                .collect()
+        };
+
+        assert_eq!(ranges(&state), [0..1, 2..3]);
+
+        // Just after a bracket counts too.
+        state.cursor = 3;
+        assert_eq!(ranges(&state), [0..1, 2..3]);
+
+        state.cursor = 5;
+        assert!(ranges(&state).is_empty());
+    }
+}
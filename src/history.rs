@@ -55,6 +55,11 @@ impl History {
         self.index = self.lines.len();
     }
 
+    /// Returns the index of the line being browsed, if any.
+    pub fn index(&self) -> Option<usize> {
+        (self.index < self.lines.len()).then_some(self.index)
+    }
+
     /// Resets the index.
     pub(crate) fn reset_index(&mut self) {
         self.index = self.lines.len();
@@ -76,6 +81,7 @@ impl History {
             self.index += 1;
             self.lines.get(self.index).cloned()
         } else {
+            self.index = self.lines.len();
             None
         }
     }
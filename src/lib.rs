@@ -1,30 +1,183 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(any(test, doctest), allow(unused))]
 
-use std::fmt::Display;
+use std::fmt;
 use std::io::{self, stdout, StdoutLock, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use antsy::AnsiStr;
 use crossterm::event::{self, Event, KeyCode, KeyEventState, KeyModifiers};
-use crossterm::{cursor, queue, terminal};
+use crossterm::terminal;
 
 mod history;
 pub use history::History;
 
-/// A highlighting scheme to apply to the user input.
+mod highlight;
+pub use highlight::{Brackets, Highlighter, Span};
+
+mod diagnostic;
+pub use diagnostic::{Diagnostic, Severity, Validator};
+
+mod printer;
+pub use printer::Printer;
+use printer::PRINT_INTERVAL;
+
+mod prompt;
+pub use prompt::Prompt;
+
+mod status;
+pub use status::Status;
+
+mod theme;
+pub use theme::Theme;
+
+mod style;
+pub use style::{Color, ColorSupport, Style};
+
+mod terminfo;
+
+pub mod command;
+pub mod matching;
+pub mod path;
+pub mod words;
+use words::Word;
+
+mod menu;
+use menu::{Cycle, Menu, SPINNER_INTERVAL};
+
+mod render;
+use render::{Frame, Line, Renderer};
+
+/// A completion to apply to the user input.
 ///
-/// The input is the current user-inputted data.
-pub type Highlight = fn(&str) -> String;
+/// Implemented for any function taking the input, the start of the
+/// selection, and the end, as char indices. The selection will be replaced
+/// in its entirety.
+///
+/// The selection is the contents of the word before the cursor, after any
+/// opening quote; see [`words`] for how words are found, and
+/// [`words::word_at`] to get the word with escapes removed. Candidates
+/// should be plain text: they are quoted or escaped when inserted,
+/// consistently with how the word was opened.
+///
+/// See [`matching`] for ready-made ways to filter and rank candidates, and
+/// [`command`] for completing a tree of commands.
+pub trait Completion {
+    /// Returns the candidates to replace `start..end` of `data` with.
+    fn complete(&self, data: &str, start: usize, end: usize) -> Vec<Candidate>;
+}
+
+impl<F: Fn(&str, usize, usize) -> Vec<Candidate>> Completion for F {
+    fn complete(&self, data: &str, start: usize, end: usize) -> Vec<Candidate> {
+        (self)(data, start, end)
+    }
+}
 
-/// A completion function to apply to the user input.
+/// A single completion candidate.
 ///
-/// The arguments are the input, the start of the selection, and the end.
-/// The selection will be replaced in its entirety.
-pub type Completion = fn(&str, usize, usize) -> Vec<String>;
+/// Can be created from a string, in which case nothing is highlighted.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Candidate {
+    /// The text to replace the selection with.
+    pub text: String,
+    /// The char indices of `text` to highlight in the completion menu,
+    /// usually those that matched the user input.
+    pub positions: Vec<usize>,
+    /// The group to show the candidate under in the completion menu.
+    pub group: Option<String>,
+}
+
+impl Candidate {
+    /// Sets the group to show the candidate under in the completion menu.
+    ///
+    /// Groups are shown in the order their first candidate appears, under
+    /// a header with their name.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{matching::Matcher, words, Candidate};
+    /// fn complete(data: &str, start: usize, end: usize) -> Vec<Candidate> {
+    ///     let word = words::word_in(data, start, end);
+    ///     let keywords = Matcher::Prefix.filter(&word, ["let", "loop"]);
+    ///     let functions = Matcher::Prefix.filter(&word, ["len", "log"]);
+    ///
+    ///     keywords
+    ///         .into_iter()
+    ///         .map(|c| c.group("keywords"))
+    ///         .chain(functions.into_iter().map(|c| c.group("functions")))
+    ///         .collect()
+    /// }
+    /// ```
+    pub fn group<S: ToString>(self, group: S) -> Self {
+        Self {
+            group: Some(group.to_string()),
+            ..self
+        }
+    }
+}
+
+impl From<String> for Candidate {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            positions: Vec::new(),
+            group: None,
+        }
+    }
+}
+
+impl From<&str> for Candidate {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_string())
+    }
+}
 
 /// The default characters on which to break words.
 pub const WORD_BREAKS: &str = "-_=+[]{}()<>,./\\`'\";:!@#$%^&*?|~ ";
 
+/// The default characters on which to break words for completion.
+pub const COMPLETION_BREAKS: &str = " ";
+
+/// What the editor is doing.
+///
+/// More modes may be added as the editor grows; it has no search or vi
+/// modes, nor multi-line input, yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Mode {
+    /// Editing the input.
+    #[default]
+    Editing,
+    /// Choosing a candidate in the completion menu.
+    Menu,
+    /// Cycling through candidates inline; see [`Editor::menu_complete`].
+    Cycling,
+}
+
+/// The state of the editor, as seen by a [`Prompt`], [`Highlighter`],
+/// [`Validator`] or [`Status`].
+///
+/// More fields may be added as the editor grows.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct State<'a> {
+    /// The current user input.
+    pub data: &'a str,
+    /// The byte index of the cursor in `data`.
+    pub cursor: usize,
+    /// What the editor is doing.
+    pub mode: Mode,
+    /// The number of lines read before this one.
+    pub line: usize,
+    /// Whether the completion menu is open.
+    pub menu_open: bool,
+    /// The candidate selected in the completion menu.
+    pub selected: Option<&'a Candidate>,
+    /// The index in the history of the line being edited, if it's from
+    /// the history.
+    pub history_index: Option<usize>,
+}
+
 /// The result of [`Editor::read`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EditResult {
@@ -46,19 +199,64 @@ pub enum EditResult {
 ///     EditResult::Quit => std::process::exit(1),
 /// }
 /// ```
-#[derive(Debug, Clone)]
-pub struct Editor<P: Display> {
+#[derive(Clone)]
+pub struct Editor<P: Prompt> {
     pub prompt: P,
+    pub rprompt: Option<Arc<dyn Prompt + Send + Sync>>,
+    pub transient_prompt: Option<Arc<dyn Prompt + Send + Sync>>,
+    pub prompt_refresh: Option<Duration>,
     pub word_breaks: Box<str>,
-    pub highlight: Option<Highlight>,
-    pub completion: Option<Completion>,
+    pub completion_breaks: Box<str>,
+    pub highlight: Option<Arc<dyn Highlighter + Send + Sync>>,
+    pub validator: Option<Arc<dyn Validator + Send + Sync>>,
+    pub status: Option<Arc<dyn Status + Send + Sync>>,
+    pub theme: Theme,
+    pub color_support: ColorSupport,
+    pub completion: Option<Arc<dyn Completion + Send + Sync>>,
+    pub background_completion: bool,
+    pub menu_complete: bool,
+    pub completion_triggers: Vec<String>,
+    pub completion_delay: Option<Duration>,
     pub history: Option<History>,
+    pub lines_read: usize,
+    pub printer: Option<Printer>,
+}
+
+impl<P: Prompt + fmt::Debug> fmt::Debug for Editor<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Editor")
+            .field("prompt", &self.prompt)
+            .field("rprompt", &self.rprompt.is_some())
+            .field("transient_prompt", &self.transient_prompt.is_some())
+            .field("prompt_refresh", &self.prompt_refresh)
+            .field("word_breaks", &self.word_breaks)
+            .field("completion_breaks", &self.completion_breaks)
+            .field("highlight", &self.highlight.is_some())
+            .field("validator", &self.validator.is_some())
+            .field("status", &self.status.is_some())
+            .field("theme", &self.theme)
+            .field("color_support", &self.color_support)
+            .field("completion", &self.completion.is_some())
+            .field("background_completion", &self.background_completion)
+            .field("menu_complete", &self.menu_complete)
+            .field("completion_triggers", &self.completion_triggers)
+            .field("completion_delay", &self.completion_delay)
+            .field("history", &self.history)
+            .field("lines_read", &self.lines_read)
+            .field("printer", &self.printer)
+            .finish()
+    }
 }
 
 // impl<P: Display> Editor<'static, P, fn(&str) -> String, fn(&str, usize, usize) -> Vec<String>> {
-impl<P: Display> Editor<P> {
+impl<P: Prompt> Editor<P> {
     /// Creates a new editor with empty highlight and default word breaks.
     ///
+    /// The prompt may span several lines, with the input following the
+    /// last one. Escape sequences in it take up no space; wrap anything
+    /// else that doesn't, such as the codes of another terminal library,
+    /// in `\x01` and `\x02` as with readline.
+    ///
     /// Example:
     /// ```
     /// # use linoleum::Editor;
@@ -67,16 +265,30 @@ impl<P: Display> Editor<P> {
     pub fn new(prompt: P) -> Self {
         Self {
             prompt,
+            rprompt: None,
+            transient_prompt: None,
+            prompt_refresh: None,
             word_breaks: Box::from(WORD_BREAKS),
+            completion_breaks: Box::from(COMPLETION_BREAKS),
             highlight: None,
+            validator: None,
+            status: None,
+            theme: Theme::default(),
+            color_support: ColorSupport::default(),
             history: None,
+            lines_read: 0,
             completion: None,
+            background_completion: false,
+            menu_complete: false,
+            completion_triggers: Vec::new(),
+            completion_delay: None,
+            printer: None,
         }
     }
 }
 
 // impl<'a, P: Display, H: Highlight, C: Completion> Editor<'a, P, H, C> {
-impl<P: Display> Editor<P> {
+impl<P: Prompt> Editor<P> {
     /// Sets the word break characters the editor respects.
     ///
     /// Example:
@@ -93,32 +305,122 @@ impl<P: Display> Editor<P> {
         }
     }
 
-    /// Sets the highlighter of the editor.
+    /// Sets the characters that break words for completion. See [`words`]
+    /// for how quotes and escapes are handled.
     ///
     /// Example:
     /// ```
     /// # use linoleum::Editor;
-    ///
-    /// // Create a new editor with a custom highlighter.
+    /// // Create a new editor that also completes after an `=`.
     /// let editor = Editor::new(" > ")
-    ///     .highlight(|data| data.replace("foo", "bar"));
+    ///     .completion_breaks(" =".into());
     /// ```
-    pub fn highlight(self, highlight: Highlight) -> Self {
+    pub fn completion_breaks(self, completion_breaks: Box<str>) -> Editor<P> {
         Editor {
-            highlight: Some(highlight),
+            completion_breaks,
             ..self
         }
     }
 
-    /// Sets the completion function.
+    /// Sets the highlighter of the editor, or any other [`Highlighter`].
     ///
     /// Example:
     /// ```
-    /// # use linoleum::{Editor, Completion};
-    /// fn complete(s: &str, _start: usize, _end: usize) -> Vec<String> {
+    /// # use linoleum::{Color, Editor, Span, Style};
+    /// // Create a new editor that shows every "foo" in red.
+    /// let editor = Editor::new(" > ").highlight(|data: &str| {
+    ///     data.match_indices("foo")
+    ///         .map(|(i, m)| Span::new(i..i + m.len(), Style::default().fg(Color::Red)))
+    ///         .collect()
+    /// });
+    /// ```
+    pub fn highlight<H: Highlighter + Send + Sync + 'static>(self, highlight: H) -> Self {
+        Editor {
+            highlight: Some(Arc::new(highlight)),
+            ..self
+        }
+    }
+
+    /// Sets the theme the editor draws its menus and messages with.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Editor, Theme};
+    /// let editor = Editor::new(" > ").theme(Theme::light());
+    /// ```
+    pub fn theme(self, theme: Theme) -> Self {
+        Editor { theme, ..self }
+    }
+
+    /// Sets the colors the terminal supports, overriding those detected
+    /// with [`ColorSupport::detect`]. The styles of the highlighter and the
+    /// theme are mapped to the closest supported colors.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{ColorSupport, Editor};
+    /// let editor = Editor::new(" > ").color_support(ColorSupport::Basic);
+    /// ```
+    pub fn color_support(self, color_support: ColorSupport) -> Self {
+        Editor {
+            color_support,
+            ..self
+        }
+    }
+
+    /// Sets the validator of the editor, or any other [`Validator`].
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Diagnostic, Editor, Severity};
+    /// // Create a new editor that warns about trailing spaces.
+    /// let editor = Editor::new(" > ").validator(|data: &str| {
+    ///     let trimmed = data.trim_end().len();
+    ///     if trimmed == data.len() {
+    ///         return Vec::new();
+    ///     }
+    ///
+    ///     vec![Diagnostic::new(trimmed..data.len(), Severity::Warning, "trailing spaces")]
+    /// });
+    /// ```
+    pub fn validator<V: Validator + Send + Sync + 'static>(self, validator: V) -> Self {
+        Editor {
+            validator: Some(Arc::new(validator)),
+            ..self
+        }
+    }
+
+    /// Sets the status line of the editor, or any other [`Status`].
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Editor, Mode, State};
+    /// let editor = Editor::new(" > ").status(|state: &State| match state.mode {
+    ///     Mode::Menu => "Enter to insert, Esc to close".to_string(),
+    ///     _ => String::new(),
+    /// });
+    /// ```
+    pub fn status<S: Status + Send + Sync + 'static>(self, status: S) -> Self {
+        Editor {
+            status: Some(Arc::new(status)),
+            ..self
+        }
+    }
+
+    /// Sets the completion function, or any other [`Completion`].
+    ///
+    /// While the completion menu is open, typing re-runs the completion
+    /// against the updated word, closing the menu once nothing matches.
+    /// The candidate selected with the arrow keys is previewed in place of
+    /// the word, and inserted with Enter.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Candidate, Editor};
+    /// fn complete(s: &str, _start: usize, _end: usize) -> Vec<Candidate> {
     ///     let hello = "hello";
     ///     if hello.starts_with(&s) {
-    ///         vec![hello.to_string()]
+    ///         vec![hello.into()]
     ///     } else {
     ///         Vec::new()
     ///     }
@@ -127,13 +429,99 @@ impl<P: Display> Editor<P> {
     /// let editor = Editor::new(" > ")
     ///     .completion(complete);
     /// ```
-    pub fn completion(self, completion: Completion) -> Self {
+    pub fn completion<C: Completion + Send + Sync + 'static>(self, completion: C) -> Self {
         Editor {
-            prompt: self.prompt,
-            word_breaks: self.word_breaks,
-            highlight: self.highlight,
-            history: self.history,
-            completion: Some(completion),
+            completion: Some(Arc::new(completion)),
+            ..self
+        }
+    }
+
+    /// Sets whether to run the completion on a background thread, for
+    /// completions that are too slow to block on.
+    ///
+    /// While it runs, the completion menu shows a spinner and input is
+    /// still accepted. Its results are discarded if the input changes
+    /// before they arrive. Completions run one at a time on a single
+    /// thread, and only the latest of those started meanwhile is run next.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{path, Editor};
+    /// let editor = Editor::new(" > ")
+    ///     .completion(path::complete)
+    ///     .background_completion(true);
+    /// ```
+    pub fn background_completion(self, background_completion: bool) -> Self {
+        Editor {
+            background_completion,
+            ..self
+        }
+    }
+
+    /// Sets whether Tab cycles through the candidates inline, as in zsh's
+    /// menu-complete.
+    ///
+    /// When set, Tab and Shift-Tab replace the word with the next or
+    /// previous candidate, highlighting it in the menu. Esc reverts to the
+    /// original word, Enter keeps the candidate, and any other key keeps
+    /// it and carries on as usual.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{path, Editor};
+    /// let editor = Editor::new(" > ")
+    ///     .completion(path::complete)
+    ///     .menu_complete(true);
+    /// ```
+    pub fn menu_complete(self, menu_complete: bool) -> Self {
+        Editor {
+            menu_complete,
+            ..self
+        }
+    }
+
+    /// Sets the strings that open the completion menu as soon as they're
+    /// typed, without pressing Tab.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Candidate, Editor};
+    /// # fn complete(_: &str, _: usize, _: usize) -> Vec<Candidate> { Vec::new() }
+    /// let editor = Editor::new(" > ")
+    ///     .completion(complete)
+    ///     .completion_breaks(" .:".into())
+    ///     .completion_triggers([".", "::"]);
+    /// ```
+    pub fn completion_triggers<I, S>(self, completion_triggers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        Editor {
+            completion_triggers: completion_triggers
+                .into_iter()
+                .map(|t| t.to_string())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Sets how long to wait after the user stops typing in the middle of
+    /// a word before opening the completion menu, without pressing Tab.
+    ///
+    /// Example:
+    /// ```
+    /// # use std::time::Duration;
+    /// # use linoleum::{Candidate, Editor};
+    /// # fn complete(_: &str, _: usize, _: usize) -> Vec<Candidate> { Vec::new() }
+    /// let editor = Editor::new(" > ")
+    ///     .completion(complete)
+    ///     .completion_delay(Duration::from_millis(300));
+    /// ```
+    pub fn completion_delay(self, completion_delay: Duration) -> Self {
+        Editor {
+            completion_delay: Some(completion_delay),
+            ..self
         }
     }
 
@@ -150,6 +538,62 @@ impl<P: Display> Editor<P> {
         self.prompt = prompt;
     }
 
+    /// Sets the prompt shown on the right side of the input, as in zsh's
+    /// RPROMPT. It's hidden while the input doesn't leave room for it.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::Editor;
+    /// let editor = Editor::new(" > ").rprompt("[main]");
+    /// ```
+    pub fn rprompt<R: Prompt + Send + Sync + 'static>(self, rprompt: R) -> Self {
+        Editor {
+            rprompt: Some(Arc::new(rprompt)),
+            ..self
+        }
+    }
+
+    /// Sets the prompt to redraw finished lines with, in place of the
+    /// prompt and the right-side prompt, to keep the scrollback compact.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::Editor;
+    /// let editor = Editor::new("~/src (main)\n > ").transient_prompt(" > ");
+    /// ```
+    pub fn transient_prompt<T: Prompt + Send + Sync + 'static>(self, transient_prompt: T) -> Self {
+        Editor {
+            transient_prompt: Some(Arc::new(transient_prompt)),
+            ..self
+        }
+    }
+
+    /// Sets how often to evaluate the prompts again while reading, for
+    /// prompts that show a clock or other changing state. The input is
+    /// kept as is. See also [`Printer::refresh_prompt`].
+    ///
+    /// Example:
+    /// ```
+    /// # use std::{fmt, time::{Duration, SystemTime, UNIX_EPOCH}};
+    /// # use linoleum::Editor;
+    /// struct Clock;
+    ///
+    /// impl fmt::Display for Clock {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    ///         write!(f, "{:02}:{:02} > ", secs / 3600 % 24, secs / 60 % 60)
+    ///     }
+    /// }
+    ///
+    /// let editor = Editor::new(Clock).prompt_refresh(Duration::from_secs(1));
+    /// ```
+    pub fn prompt_refresh(self, prompt_refresh: Duration) -> Self {
+        Editor {
+            prompt_refresh: Some(prompt_refresh),
+            ..self
+        }
+    }
+
     /// Sets the file to use for history.
     ///
     /// Opens and reads the file immediately.
@@ -166,6 +610,12 @@ impl<P: Display> Editor<P> {
         Ok(self)
     }
 
+    /// Returns a handle to print with while the editor is reading, from
+    /// any thread. See [`Printer`].
+    pub fn printer(&mut self) -> Printer {
+        self.printer.get_or_insert_with(Printer::default).clone()
+    }
+
     /// Resets the history index to the most recent.
     ///
     /// Example:
@@ -211,26 +661,110 @@ impl<P: Display> Editor<P> {
     /// }
     /// ```
     pub fn read(&mut self) -> io::Result<EditResult> {
+        let _reading = self.printer.as_ref().map(Printer::start);
         let mut stdout = stdout().lock();
 
-        let prompt = self.prompt.to_string();
-        let prompt_length = AnsiStr::new(&prompt).len();
+        let (mut prompt, mut rprompt) = (Vec::new(), Vec::new());
+        // What the prompts last saw, if they use it, and when to evaluate
+        // them again regardless.
+        let mut seen: Option<Seen> = None;
+        let mut refresh_at = self.prompt_refresh.map(|d| Instant::now() + d);
 
-        write!(stdout, "{}", prompt)?;
-        stdout.flush()?;
         terminal::enable_raw_mode()?;
 
+        let mut renderer = match terminal::size() {
+            Ok((width, _)) => Renderer::new(width as usize),
+            Err(e) => {
+                terminal::disable_raw_mode()?;
+                return Err(e);
+            }
+        };
+
         let mut data = String::new();
         let mut cursor = 0;
 
-        let mut cursor_line = 0;
-        let mut num_lines = 0;
+        let mut menu = Menu::new(self.styles());
+        // Whether the input shows a preview of the selected candidate.
+        let mut previewing = false;
+        // When to open the completion menu, if the user stops typing.
+        let mut complete_at: Option<Instant> = None;
+        // Whether to start cycling once the background completion started
+        // by Tab finishes, and in which direction.
+        let mut cycle_on_load: Option<bool> = None;
 
-        let mut completion_length = 0;
-        let mut completions = Vec::<String>::new();
-        let mut completion_index = 0;
+        // Whether anything changed since the last frame, other than what
+        // the printer and refreshing the prompt bring.
+        let mut redraw = true;
 
         loop {
+            let printed = self.printer.as_ref().map(Printer::take).unwrap_or_default();
+            let refresh = seen.is_none()
+                || self.printer.as_ref().is_some_and(Printer::take_refresh)
+                || refresh_at.is_some_and(|at| at <= Instant::now());
+
+            if refresh {
+                refresh_at = self.prompt_refresh.map(|d| Instant::now() + d);
+            }
+
+            if redraw || refresh || !printed.is_empty() {
+                let state = self.state(&data, &menu, cursor);
+                if refresh || (self.uses_state() && seen.as_ref() != Some(&Seen::new(&state))) {
+                    self.update_prompts(&state, &mut prompt, &mut rprompt, refresh);
+                    seen = Some(Seen::new(&state));
+                }
+
+                let frame = self.frame(&prompt, &rprompt, &data, cursor, &menu, previewing);
+                if let Err(e) = self.render(&mut stdout, &mut renderer, &printed, &frame) {
+                    terminal::disable_raw_mode()?;
+                    return Err(e);
+                }
+
+                redraw = false;
+            }
+
+            // While completing in the background, wake up regularly to
+            // animate the spinner and pick up the result. Also wake up
+            // once it's time to complete as-you-type or refresh the prompt.
+            let mut timeout = menu.is_loading().then_some(SPINNER_INTERVAL);
+            if self.printer.is_some() {
+                timeout = Some(timeout.map_or(PRINT_INTERVAL, |t| t.min(PRINT_INTERVAL)));
+            }
+            for at in complete_at.into_iter().chain(refresh_at) {
+                let left = at.saturating_duration_since(Instant::now());
+                timeout = Some(timeout.map_or(left, |t| t.min(left)));
+            }
+
+            if let Some(timeout) = timeout {
+                match event::poll(timeout) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        if complete_at.is_some_and(|at| at <= Instant::now()) {
+                            complete_at = None;
+                            if !menu.is_open() {
+                                self.open_menu(&mut menu, &data, cursor);
+                                redraw = true;
+                            }
+                        } else if menu.is_loading() {
+                            if menu.poll(&data, cursor) {
+                                if let Some(backwards) = cycle_on_load.take() {
+                                    self.start_cycle(&mut menu, &data, cursor, backwards);
+                                    self.place_cycle(&menu, &mut data, &mut cursor);
+                                }
+                            }
+
+                            // The spinner moved on, if nothing else.
+                            redraw = true;
+                        }
+
+                        continue;
+                    }
+                    Err(e) => {
+                        terminal::disable_raw_mode()?;
+                        return Err(e);
+                    }
+                }
+            }
+
             let ev = event::read();
 
             let ev = match ev {
@@ -240,68 +774,57 @@ impl<P: Display> Editor<P> {
                     return Err(e);
                 }
             };
+            redraw = true;
+
+            if let Event::Resize(width, _) = ev {
+                if let Err(e) = renderer.resize(&mut stdout, width as usize) {
+                    terminal::disable_raw_mode()?;
+                    return Err(e);
+                }
+            }
 
             if let Event::Key(key) = ev {
                 let caps = key.modifiers.contains(KeyModifiers::SHIFT)
                     ^ key.state.contains(KeyEventState::CAPS_LOCK);
 
+                // Whether the key narrows or widens the completion menu,
+                // rather than closing it.
+                let mut filtering = false;
+                // Whether the selection in the completion menu moved.
+                let mut navigated = false;
+                // Whether a character was typed.
+                let mut typed = false;
+                complete_at = None;
+                previewing = false;
+                let awaiting_cycle = cycle_on_load.take();
+
+                let word_start = menu
+                    .is_open()
+                    .then(|| self.completion_word(&data, cursor).start);
+
                 match key.code {
                     KeyCode::Enter => {
-                        if completion_length != 0 {
-                            let old_cursor = cursor;
-                            cursor = self.find_space_boundary(&data, cursor, true);
-                            if let Some(ch) = data.chars().nth(cursor) {
-                                if self.word_breaks.contains(ch) {
-                                    cursor += 1;
-                                }
-                            }
-
-                            data = data
-                                .chars()
-                                .take(cursor)
-                                .chain(data.chars().skip(old_cursor))
-                                .collect();
-
-                            data.insert_str(cursor, completions[completion_index].as_str());
-                            cursor += completions[completion_index].len();
-
-                            self.redraw(
-                                &mut stdout,
-                                &data,
-                                prompt_length,
-                                &mut cursor_line,
-                                &mut num_lines,
-                                cursor,
-                            )?;
+                        if menu.cycle.is_some() {
+                            // The candidate is already in place.
+                        } else if let Some(completion) = menu.selected() {
+                            let word = self.completion_word(&data, cursor);
+                            let completion =
+                                words::quote(&completion.text, word.quote, &self.completion_breaks);
+
+                            data = replace_chars(&data, word.start, cursor, &completion);
+                            cursor = word.start + completion.chars().count();
                         } else {
                             break;
                         }
                     }
-                    KeyCode::Backspace => {
-                        if cursor != 0 {
-                            cursor -= 1;
-                            data.remove(cursor);
-                            self.redraw(
-                                &mut stdout,
-                                &data,
-                                prompt_length,
-                                &mut cursor_line,
-                                &mut num_lines,
-                                cursor,
-                            )?;
-                        }
+                    KeyCode::Backspace if cursor != 0 => {
+                        filtering = menu.is_open() && menu.cycle.is_none();
+
+                        cursor -= 1;
+                        data.remove(byte_index(&data, cursor));
                     }
                     KeyCode::Char(mut ch) => {
                         if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            if completion_length != 0 {
-                                self.clear_completions(
-                                    &mut stdout,
-                                    completion_length,
-                                    cursor_line,
-                                    num_lines,
-                                )?;
-                            }
-
                             if ch == 'h' {
                                 let old_cursor = cursor;
                                 cursor = self.find_word_boundary(&data, cursor, true);
@@ -311,28 +834,19 @@ impl<P: Display> Editor<P> {
                                     .take(cursor)
                                     .chain(data.chars().skip(old_cursor))
                                     .collect();
-
-                                self.redraw(
-                                    &mut stdout,
-                                    &data,
-                                    prompt_length,
-                                    &mut cursor_line,
-                                    &mut num_lines,
-                                    cursor,
-                                )?;
                             } else if ch == 'd' {
+                                self.finish(&mut stdout, &mut renderer, &prompt, &rprompt, &data)?;
                                 terminal::disable_raw_mode()?;
                                 self.reset_history_index();
-                                writeln!(stdout)?;
                                 return Ok(if data.is_empty() {
                                     EditResult::Quit
                                 } else {
                                     EditResult::Cancel
                                 });
                             } else if ch == 'c' {
+                                self.finish(&mut stdout, &mut renderer, &prompt, &rprompt, &data)?;
                                 terminal::disable_raw_mode()?;
                                 self.reset_history_index();
-                                writeln!(stdout)?;
                                 return Ok(EditResult::Cancel);
                             }
                         } else {
@@ -340,206 +854,142 @@ impl<P: Display> Editor<P> {
                                 ch = ch.to_uppercase().next().unwrap();
                             }
 
-                            data.insert(cursor, ch);
+                            filtering = menu.is_open() && menu.cycle.is_none();
+                            typed = true;
+
+                            data.insert(byte_index(&data, cursor), ch);
                             cursor += 1;
-                            self.redraw(
-                                &mut stdout,
-                                &data,
-                                prompt_length,
-                                &mut cursor_line,
-                                &mut num_lines,
-                                cursor,
-                            )?;
                         }
                     }
                     KeyCode::Left => {
-                        if completion_length != 0 {
-                            completion_index = completion_index.saturating_sub(1);
-
-                            self.clear_completions(
-                                &mut stdout,
-                                completion_length,
-                                cursor_line,
-                                num_lines,
-                            )?;
-
-                            completion_length = self.show_completions(
-                                &mut stdout,
-                                &completions,
-                                cursor_line,
-                                num_lines,
-                                completion_index,
-                            )?;
-
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        if !menu.completions.is_empty() {
+                            menu.select(-1);
+                            navigated = true;
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
                             cursor = self.find_word_boundary(&data, cursor, true);
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
-                        } else if cursor != 0 {
-                            cursor -= 1;
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        } else {
+                            cursor = cursor.saturating_sub(1);
                         }
                     }
                     KeyCode::Right => {
-                        if completion_length != 0 {
-                            completion_index = completion_index.saturating_add(1);
-
-                            self.clear_completions(
-                                &mut stdout,
-                                completion_length,
-                                cursor_line,
-                                num_lines,
-                            )?;
-
-                            completion_length = self.show_completions(
-                                &mut stdout,
-                                &completions,
-                                cursor_line,
-                                num_lines,
-                                completion_index,
-                            )?;
-
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        if !menu.completions.is_empty() {
+                            menu.select(1);
+                            navigated = true;
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            cursor = self.find_word_boundary(&data, cursor, false) + 1;
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
-                        } else if cursor != data.len() {
+                            cursor = self.find_word_boundary(&data, cursor, false);
+                        } else if cursor != data.chars().count() {
                             cursor += 1;
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
                         }
                     }
                     KeyCode::Up => {
-                        if completion_length != 0 {
-                            completion_index = completion_index.saturating_sub(2);
-
-                            self.clear_completions(
-                                &mut stdout,
-                                completion_length,
-                                cursor_line,
-                                num_lines,
-                            )?;
-
-                            completion_length = self.show_completions(
-                                &mut stdout,
-                                &completions,
-                                cursor_line,
-                                num_lines,
-                                completion_index,
-                            )?;
-
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        if !menu.completions.is_empty() {
+                            menu.select_row(-1);
+                            navigated = true;
                         } else if let Some(h) = &mut self.history {
                             if let Some(line) = h.up() {
                                 data = line;
-                                cursor = data.len();
-                                self.redraw(
-                                    &mut stdout,
-                                    &data,
-                                    prompt_length,
-                                    &mut cursor_line,
-                                    &mut num_lines,
-                                    cursor,
-                                )?;
+                                cursor = data.chars().count();
                             }
                         }
                     }
                     KeyCode::Down => {
-                        if completion_length != 0 {
-                            completion_index = completion_index.saturating_add(2);
-
-                            self.clear_completions(
-                                &mut stdout,
-                                completion_length,
-                                cursor_line,
-                                num_lines,
-                            )?;
-
-                            completion_length = self.show_completions(
-                                &mut stdout,
-                                &completions,
-                                cursor_line,
-                                num_lines,
-                                completion_index,
-                            )?;
-
-                            self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        if !menu.completions.is_empty() {
+                            menu.select_row(1);
+                            navigated = true;
                         } else if let Some(h) = &mut self.history {
                             if let Some(line) = h.down() {
                                 data = line;
-                                cursor = data.len();
-                                self.redraw(
-                                    &mut stdout,
-                                    &data,
-                                    prompt_length,
-                                    &mut cursor_line,
-                                    &mut num_lines,
-                                    cursor,
-                                )?;
+                                cursor = data.chars().count();
                             } else {
                                 data.clear();
                                 cursor = 0;
-                                self.redraw(
-                                    &mut stdout,
-                                    &data,
-                                    prompt_length,
-                                    &mut cursor_line,
-                                    &mut num_lines,
-                                    cursor,
-                                )?;
                             }
                         }
                     }
                     KeyCode::Home => {
                         cursor = 0;
-                        self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
                     }
                     KeyCode::End => {
-                        cursor = data.len();
-                        self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        cursor = data.chars().count();
                     }
-                    KeyCode::Tab => {
-                        let word_start = self.find_space_boundary(&data, cursor, true);
-                        if let Some(c) = &mut self.completion {
-                            completions = (c)(&data, word_start, cursor);
-                        } else {
-                            continue;
+                    KeyCode::Tab | KeyCode::BackTab
+                        if self.menu_complete && self.completion.is_some() =>
+                    {
+                        let backwards = key.code == KeyCode::BackTab;
+                        if menu.is_loading() {
+                            // Cycle once the completion already running finishes.
+                            cycle_on_load = awaiting_cycle.or(Some(backwards));
+                        } else if menu.completions.is_empty() {
+                            self.open_menu(&mut menu, &data, cursor);
+                            if menu.is_loading() {
+                                cycle_on_load = Some(backwards);
+                            }
+                        } else if menu.cycle.is_some() {
+                            menu.select_wrapping(if backwards { -1 } else { 1 });
                         }
 
-                        if completion_length != 0 {
-                            self.clear_completions(
-                                &mut stdout,
-                                completion_length,
-                                cursor_line,
-                                num_lines,
-                            )?;
+                        if menu.cycle.is_none() {
+                            self.start_cycle(&mut menu, &data, cursor, backwards);
                         }
 
-                        completion_length = self.show_completions(
-                            &mut stdout,
-                            &completions,
-                            cursor_line,
-                            num_lines,
-                            completion_index,
-                        )?;
+                        navigated = true;
+                    }
+                    KeyCode::Esc => {
+                        if let Some(cycle) = menu.cycle.take() {
+                            data = replace_chars(&data, cycle.start, cursor, &cycle.original);
+                            cursor = cycle.start + cycle.original.chars().count();
+                        }
+                    }
+                    KeyCode::Tab => {
+                        if self.completion.is_none() || menu.is_loading() {
+                            continue;
+                        }
 
-                        self.move_to(&mut stdout, prompt_length, &mut cursor_line, cursor)?;
+                        self.open_menu(&mut menu, &data, cursor);
                     }
                     _ => {}
                 }
 
-                if completion_length != 0
-                    && !matches!(
-                        key.code,
-                        KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down
-                    )
-                {
-                    self.clear_completions(&mut stdout, completion_length, cursor_line, num_lines)?;
-                    completion_length = 0;
-                    completion_index = 0;
+                // When cycling, the selected candidate goes in place of the
+                // word. Otherwise, it's previewed there until the next key.
+                if navigated {
+                    if menu.cycle.is_some() {
+                        self.place_cycle(&menu, &mut data, &mut cursor);
+                    } else {
+                        previewing = menu.selected().is_some();
+                    }
+                }
+
+                // Re-run the completion against the updated word, unless
+                // it was ended; the menu closes itself once nothing matches.
+                // Keys moving the cursor rather than the selection, such as
+                // while loading, close it too.
+                if filtering && word_start == Some(self.completion_word(&data, cursor).start) {
+                    self.open_menu(&mut menu, &data, cursor);
+                } else if !navigated && !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+                    menu.close();
+                }
+
+                // Complete as-you-type, either right after a trigger or
+                // once the user stops typing in the middle of a word.
+                if typed && !menu.is_open() && self.completion.is_some() {
+                    let before: String = data.chars().take(cursor).collect();
+                    if self
+                        .completion_triggers
+                        .iter()
+                        .any(|trigger| before.ends_with(trigger.as_str()))
+                    {
+                        self.open_menu(&mut menu, &data, cursor);
+                    } else if let Some(delay) = self.completion_delay {
+                        if !self.completion_word(&data, cursor).text.is_empty() {
+                            complete_at = Some(Instant::now() + delay);
+                        }
+                    }
                 }
             }
         }
 
+        self.finish(&mut stdout, &mut renderer, &prompt, &rprompt, &data)?;
         terminal::disable_raw_mode()?;
         self.reset_history_index();
 
@@ -547,153 +997,77 @@ impl<P: Display> Editor<P> {
             h.push(data.clone());
         }
 
-        writeln!(stdout)?;
+        self.lines_read += 1;
         Ok(EditResult::Ok(data))
     }
 
-    fn clear_completions(
-        &self,
-        stdout: &mut StdoutLock,
-        completion_length: u16,
-        cursor_line: u16,
-        num_lines: u16,
-    ) -> io::Result<()> {
-        if completion_length == 0 {
-            return Ok(());
-        }
-
-        let n = num_lines - cursor_line;
-
-        if n != 0 {
-            queue!(stdout, cursor::MoveDown(n))?;
-        }
-
-        for _ in 0..completion_length {
-            queue!(
-                stdout,
-                cursor::MoveDown(1),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-            )?;
-        }
-
-        queue!(stdout, cursor::MoveUp(completion_length))?;
+    /// Runs the completion on the word under the cursor, or starts running
+    /// it in the background if enabled.
+    fn open_menu(&self, menu: &mut Menu, data: &str, cursor: usize) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
 
-        if n != 0 {
-            queue!(stdout, cursor::MoveUp(n),)?;
+        let start = self.completion_word(data, cursor).content_start();
+        if self.background_completion {
+            menu.load(completion.clone(), data, start, cursor);
+        } else {
+            menu.set(completion.complete(data, start, cursor));
         }
-
-        stdout.flush()
     }
 
-    fn show_completions(
-        &self,
-        stdout: &mut StdoutLock,
-        completions: &[String],
-        cursor_line: u16,
-        num_lines: u16,
-        completion_index: usize,
-    ) -> io::Result<u16> {
-        if completions.is_empty() {
-            return Ok(0);
-        }
-
-        let n = num_lines - cursor_line;
-
-        if n != 0 {
-            queue!(stdout, cursor::MoveDown(n))?;
+    /// Starts cycling through the candidates inline, from the first or,
+    /// going `backwards`, the last. Does nothing without any candidates.
+    fn start_cycle(&self, menu: &mut Menu, data: &str, cursor: usize, backwards: bool) {
+        if menu.completions.is_empty() {
+            return;
         }
 
-        let mut width = 0;
-        for c in completions.chunks(2) {
-            let l = &c[0];
-            let r = c.get(1);
+        let word = self.completion_word(data, cursor);
+        menu.cycle = Some(Cycle {
+            start: word.start,
+            quote: word.quote,
+            original: data.chars().take(cursor).skip(word.start).collect(),
+        });
 
-            width = width.max(l.len() + r.map_or(0, |s| s.len()));
+        if backwards {
+            menu.select_wrapping(-1);
         }
-
-        let completions = completions.chunks(2);
-
-        let mut moved = 0;
-        let mut idx = 0;
-        for c in completions {
-            let l = &c[0];
-            let r = c.get(1);
-
-            write!(
-                stdout,
-                "\r\n {}{l:0width$}\x1b[0m",
-                if idx == completion_index {
-                    "\x1b[38;5;6m"
-                } else {
-                    ""
-                },
-                width = width - r.map_or(0, |s| s.len()),
-            )?;
-
-            idx += 1;
-
-            if let Some(r) = r {
-                write!(
-                    stdout,
-                    " {}{r}\x1b[0m",
-                    if idx == completion_index {
-                        "\x1b[38;5;6m"
-                    } else {
-                        ""
-                    },
-                )?;
-            }
-
-            idx += 1;
-            moved += 1;
-        }
-
-        if moved != 0 {
-            queue!(stdout, cursor::MoveUp(moved))?;
-        }
-
-        if n != 0 {
-            queue!(stdout, cursor::MoveUp(n),)?;
-        }
-
-        stdout.flush()?;
-
-        Ok(moved)
     }
 
-    /// Finds a word boundary, but only delimited by spaces.
-    fn find_space_boundary(&self, data: &str, start: usize, backwards: bool) -> usize {
-        let chars: Vec<char> = data.chars().collect();
-        let (step, stop) = if backwards {
-            (-1, 0)
-        } else {
-            (1, data.len() as i64 - 1)
+    /// Puts the candidate selected while cycling in place of the word, or
+    /// the original word if none is.
+    fn place_cycle(&self, menu: &Menu, data: &mut String, cursor: &mut usize) {
+        let Some(cycle) = &menu.cycle else {
+            return;
         };
 
-        let mut i = start as i64;
-
-        while i != stop {
-            i += step;
-
-            if chars[i as usize] == ' ' {
-                if start as i64 - i > 1 {
-                    i -= step;
-                }
+        let text = match menu.selected() {
+            Some(c) => words::quote(&c.text, cycle.quote, &self.completion_breaks),
+            None => cycle.original.clone(),
+        };
 
-                break;
-            }
-        }
+        *data = replace_chars(data, cycle.start, *cursor, &text);
+        *cursor = cycle.start + text.chars().count();
+    }
 
-        i as usize
+    /// Finds the word being completed.
+    fn completion_word(&self, data: &str, cursor: usize) -> Word {
+        words::word_at(data, cursor, &self.completion_breaks)
     }
 
-    /// Finds a word boundary.
+    /// Finds the char index of the word boundary before `start`, or past
+    /// the one after it going forwards, staying within the input.
     fn find_word_boundary(&self, data: &str, start: usize, backwards: bool) -> usize {
         let chars: Vec<char> = data.chars().collect();
+        if !backwards && start + 1 >= chars.len() {
+            return chars.len();
+        }
+
         let (step, stop) = if backwards {
             (-1, 0)
         } else {
-            (1, data.len() as i64 - 1)
+            (1, chars.len() as i64 - 1)
         };
 
         let mut i = start as i64;
@@ -710,147 +1084,253 @@ impl<P: Display> Editor<P> {
             }
         }
 
-        i as usize
+        if backwards {
+            i as usize
+        } else {
+            i as usize + 1
+        }
     }
 
-    /// Moves the visual cursor to the appropriate position.
-    fn move_to(
-        &self,
-        stdout: &mut StdoutLock,
-        prompt_length: usize,
-        cursor_line: &mut u16,
-        end: usize,
-    ) -> io::Result<()> {
-        let size = terminal::size()?.0;
-
-        let end = end + prompt_length;
-        queue!(stdout, cursor::MoveToColumn(end as u16 % size as u16))?;
+    /// Whether the prompt or the right-side prompt uses the state.
+    fn uses_state(&self) -> bool {
+        self.prompt.uses_state() || self.rprompt.as_ref().is_some_and(|p| p.uses_state())
+    }
 
-        let move_up = *cursor_line as i32 - end as i32 / size as i32;
-        let m = move_up.unsigned_abs() as u16;
-        #[allow(clippy::comparison_chain)]
-        if move_up > 0 {
-            queue!(stdout, cursor::MoveUp(m))?;
-            *cursor_line -= m;
-        } else if move_up < 0 {
-            queue!(stdout, cursor::MoveDown(m))?;
-            *cursor_line += m;
+    /// Evaluates the prompt and the right-side prompt again, or only those
+    /// that use the state unless `all` is set.
+    fn update_prompts(&self, state: &State, prompt: &mut Vec<Line>, rprompt: &mut Line, all: bool) {
+        if all || self.prompt.uses_state() {
+            *prompt = render::lines(&self.prompt.render(state));
         }
 
-        stdout.flush()
+        match &self.rprompt {
+            Some(p) if all || p.uses_state() => *rprompt = render::raw(&p.render(state)),
+            _ => {}
+        }
     }
 
-    /// Redraws the user input, updating the cursor_line and num_lines
-    /// variables appropriately.
-    fn redraw(
-        &mut self,
-        stdout: &mut StdoutLock,
+    /// Lays out the prompts, the input with the cursor at the char index
+    /// `cursor`, the message of the most relevant diagnostic, the status,
+    /// and the completion menu.
+    fn frame(
+        &self,
+        prompt: &[Line],
+        rprompt: &Line,
         data: &str,
-        prompt_length: usize,
-        cursor_line: &mut u16,
-        num_lines: &mut u16,
-        end: usize,
-    ) -> io::Result<()> {
-        self.clear(stdout, prompt_length, *cursor_line, *num_lines)?;
+        cursor: usize,
+        menu: &Menu,
+        previewing: bool,
+    ) -> Frame {
+        let theme = self.styles();
+        let state = self.state(data, menu, cursor);
+
+        let mut spans = match &self.highlight {
+            Some(h) => h.highlight(&state),
+            None => Vec::new(),
+        };
 
-        let data_length = data.len();
-        let data = if let Some(h) = &mut self.highlight {
-            (h)(data)
-        } else {
-            data.to_string()
+        let diagnostics = match &self.validator {
+            Some(v) if !previewing => v.validate(&state),
+            _ => Vec::new(),
         };
 
-        let ansi_str = AnsiStr::new(&data);
-        let mut data = 0..ansi_str.len();
+        spans.extend(
+            diagnostics
+                .iter()
+                .map(|d| Span::new(d.range.clone(), theme.diagnostic(d.severity))),
+        );
 
-        let size = terminal::size()?.0;
+        for span in &mut spans {
+            span.style = span.style.downsample(self.color_support);
+        }
 
-        let mut cap = ansi_str.len().min(size as usize - prompt_length);
-        write!(stdout, "{}", ansi_str.get(data.start..cap))?;
+        let mut input = highlight::render(data, &spans);
+        let mut end = cursor;
 
-        *num_lines = 0;
-        *cursor_line = 0;
-        let length = data_length + prompt_length;
-        if length > size as usize {
-            loop {
-                data = cap..ansi_str.len();
-                if data.is_empty() {
-                    break;
-                }
+        // Show the selected candidate in place of the word.
+        if let Some(c) = menu.selected().filter(|_| previewing) {
+            let word = self.completion_word(data, cursor);
+            let text = words::quote(&c.text, word.quote, &self.completion_breaks);
 
-                cap = data_length.min(size as usize);
-                write!(stdout, "\r\n{}", ansi_str.get(data.start..cap))?;
-                *num_lines += 1;
-                *cursor_line += 1;
-            }
+            input.splice(word.start..cursor, render::styled(&text, theme.preview));
+            end = word.start + text.chars().count();
+        }
 
-            let end = end + prompt_length;
-            queue!(stdout, cursor::MoveToColumn((end % size as usize) as u16))?;
-
-            let move_up = *num_lines as i32 - (end / size as usize) as i32;
-            let m = move_up.unsigned_abs() as u16;
-            #[allow(clippy::comparison_chain)]
-            if move_up > 0 {
-                queue!(stdout, cursor::MoveUp(m))?;
-                *cursor_line -= m;
-            } else if move_up < 0 {
-                queue!(stdout, cursor::MoveDown(m))?;
-                *cursor_line += m;
-            }
-        } else if length == size as usize && end == data_length {
-            queue!(stdout, cursor::MoveDown(1), cursor::MoveToColumn(0))?;
+        // The input goes on the last line of the prompt.
+        let mut lines = prompt.to_vec();
+        let row = lines.len() - 1;
+        let line = &mut lines[row];
+        let column = line.len() + end;
+        render::extend(line, input);
+
+        // Show the message of the most severe problem, preferring those at
+        // the cursor.
+        let at_cursor =
+            |d: &Diagnostic| d.range.start <= state.cursor && state.cursor <= d.range.end;
+        if let Some(d) = diagnostics
+            .iter()
+            .rev()
+            .max_by_key(|d| (d.severity, at_cursor(d)))
+        {
+            let message = format!("{}: {}", d.severity, d.message);
+            lines.push(render::styled(&message, theme.message(d.severity)));
+        }
 
-            *num_lines += 1;
-            *cursor_line += 1;
-        } else {
-            queue!(stdout, cursor::MoveToColumn((end + prompt_length) as u16))?;
+        if let Some(status) = &self.status {
+            let status = status.status(&state);
+            if !status.is_empty() {
+                lines.extend(render::lines(&status).into_iter().map(|mut line| {
+                    line.iter_mut().for_each(|cell| cell.style = theme.status);
+                    line
+                }));
+            }
         }
 
-        stdout.flush()
+        lines.extend(menu.lines());
+
+        Frame {
+            lines,
+            cursor: (row, column),
+            right: rprompt.clone(),
+        }
     }
 
-    fn clear(
+    /// Draws a frame over the last one, after printing `printed`, the text
+    /// sent to the printer, above it.
+    fn render(
         &self,
         stdout: &mut StdoutLock,
-        prompt_length: usize,
-        cursor_line: u16,
-        num_lines: u16,
+        renderer: &mut Renderer,
+        printed: &str,
+        frame: &Frame,
     ) -> io::Result<()> {
-        if cursor_line != 0 {
-            queue!(stdout, cursor::MoveUp(cursor_line),)?;
+        if !printed.is_empty() {
+            renderer.clear(stdout)?;
+            stdout.write_all(printed.as_bytes())?;
         }
 
-        queue!(
-            stdout,
-            cursor::MoveToColumn(prompt_length as u16),
-            terminal::Clear(terminal::ClearType::UntilNewLine),
-        )?;
+        renderer.render(stdout, frame)
+    }
 
-        if num_lines == 0 {
-            return Ok(());
-        }
+    /// Draws the input one last time, without anything below it and with
+    /// the transient prompt if there is one, and moves past it.
+    fn finish(
+        &self,
+        stdout: &mut StdoutLock,
+        renderer: &mut Renderer,
+        prompt: &[Line],
+        rprompt: &Line,
+        data: &str,
+    ) -> io::Result<()> {
+        let menu = Menu::new(self.styles());
+        let state = self.state(data, &menu, data.chars().count());
+        let (prompt, rprompt) = match &self.transient_prompt {
+            Some(p) => (render::lines(&p.render(&state)), Vec::new()),
+            None => {
+                let (mut prompt, mut rprompt) = (prompt.to_vec(), rprompt.clone());
+                self.update_prompts(&state, &mut prompt, &mut rprompt, false);
+                (prompt, rprompt)
+            }
+        };
 
-        for _ in 0..num_lines {
-            queue!(
-                stdout,
-                cursor::MoveDown(1),
-                cursor::MoveToColumn(0),
-                terminal::Clear(terminal::ClearType::CurrentLine),
-            )?;
+        let mut frame = self.frame(&prompt, &rprompt, data, data.chars().count(), &menu, false);
+        frame.lines.truncate(frame.cursor.0 + 1);
+
+        let printed = self.printer.as_ref().map(Printer::take).unwrap_or_default();
+        self.render(stdout, renderer, &printed, &frame)?;
+        renderer.leave(stdout)
+    }
+
+    /// The state of the editor, with the cursor at the char index `cursor`.
+    fn state<'a>(&self, data: &'a str, menu: &'a Menu, cursor: usize) -> State<'a> {
+        State {
+            data,
+            cursor: byte_index(data, cursor),
+            mode: if menu.cycle.is_some() {
+                Mode::Cycling
+            } else if menu.is_open() {
+                Mode::Menu
+            } else {
+                Mode::Editing
+            },
+            line: self.lines_read,
+            menu_open: menu.is_open(),
+            selected: menu.selected(),
+            history_index: self.history.as_ref().and_then(History::index),
         }
+    }
 
-        queue!(
-            stdout,
-            cursor::MoveUp(num_lines),
-            cursor::MoveToColumn(prompt_length as u16),
-        )?;
+    /// The theme, with its colors mapped to those the terminal supports.
+    fn styles(&self) -> Theme {
+        self.theme.downsample(self.color_support)
+    }
+}
 
-        Ok(())
+/// What a [`Prompt`] last saw of the state, to tell when it changes.
+#[derive(PartialEq)]
+struct Seen {
+    data: String,
+    cursor: usize,
+    mode: Mode,
+    selected: Option<Candidate>,
+    history_index: Option<usize>,
+}
+
+impl Seen {
+    fn new(state: &State) -> Self {
+        Self {
+            data: state.data.to_string(),
+            cursor: state.cursor,
+            mode: state.mode,
+            selected: state.selected.cloned(),
+            history_index: state.history_index,
+        }
     }
 }
 
+/// Converts the char index `cursor` into a byte index into `data`.
+fn byte_index(data: &str, cursor: usize) -> usize {
+    data.char_indices()
+        .nth(cursor)
+        .map_or(data.len(), |(i, _)| i)
+}
+
+/// Replaces the chars `start..end` of `data` with `text`.
+fn replace_chars(data: &str, start: usize, end: usize, text: &str) -> String {
+    data.chars()
+        .take(start)
+        .chain(text.chars())
+        .chain(data.chars().skip(end))
+        .collect()
+}
+
 // impl<'a, P: Display, H: Highlight, C: Completion> Drop for Editor<'a, P, H, C> {
 // fn drop(&mut self) {
 // self.save_history().expect("failed to save history");
 // }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_boundary_forwards() {
+        let editor = Editor::new(" > ");
+        assert_eq!(editor.find_word_boundary("ab cd", 0, false), 3);
+        assert_eq!(editor.find_word_boundary("ab cd", 3, false), 5);
+
+        // At the end, or with nothing to move over, it stays in place.
+        assert_eq!(editor.find_word_boundary("ab cd", 4, false), 5);
+        assert_eq!(editor.find_word_boundary("ab cd", 5, false), 5);
+        assert_eq!(editor.find_word_boundary("", 0, false), 0);
+    }
+
+    #[test]
+    fn word_boundary_backwards() {
+        let editor = Editor::new(" > ");
+        assert_eq!(editor.find_word_boundary("ab cd", 5, true), 3);
+        assert_eq!(editor.find_word_boundary("éé éé", 5, true), 3);
+        assert_eq!(editor.find_word_boundary("", 0, true), 0);
+    }
+}
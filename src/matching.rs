@@ -0,0 +1,308 @@
+//! Filtering and ranking of completion candidates.
+//!
+//! Example:
+//! ```
+//! use linoleum::{Candidate, matching::Matcher, words};
+//!
+//! fn complete(data: &str, start: usize, end: usize) -> Vec<Candidate> {
+//!     let word = words::word_in(data, start, end);
+//!     Matcher::Fuzzy.filter(&word, ["print", "println", "eprint", "format"])
+//! }
+//!
+//! let found = complete("pln", 0, 3);
+//! assert_eq!(found[0].text, "println");
+//! assert_eq!(found[0].positions, [0, 5, 6]);
+//!
+//! // `start` and `end` are char indices, not byte ones.
+//! let found = complete("é pln", 2, 5);
+//! assert_eq!(found[0].text, "println");
+//! ```
+
+use crate::Candidate;
+
+const SCORE_MATCH: i64 = 16;
+const PENALTY_GAP_START: i64 = -3;
+const PENALTY_GAP_EXTENSION: i64 = -1;
+const BONUS_WHITESPACE: i64 = 10;
+const BONUS_DELIMITER: i64 = 9;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+
+/// The characters after which a match counts as starting a new word.
+const DELIMITERS: &str = "/\\,:;|-_.=";
+
+/// A strategy for matching a pattern against candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Matcher {
+    /// The candidate must start with the pattern.
+    #[default]
+    Prefix,
+    /// The candidate must start with the pattern, ignoring case.
+    PrefixIgnoreCase,
+    /// The pattern must be a subsequence of the candidate. Matches are
+    /// scored similarly to fzf and skim, favoring consecutive characters
+    /// and the starts of words.
+    ///
+    /// Uses smart case: the match ignores case unless the pattern contains
+    /// an uppercase character.
+    Fuzzy,
+}
+
+/// The result of matching a pattern against a single candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// How well the candidate matched; higher is better.
+    pub score: i64,
+    /// The char indices of the candidate that matched the pattern.
+    pub positions: Vec<usize>,
+}
+
+impl Matcher {
+    /// Matches a pattern against a single candidate, returning `None` if
+    /// it doesn't match.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::matching::Matcher;
+    /// assert!(Matcher::Prefix.matches("He", "hello").is_none());
+    /// assert!(Matcher::PrefixIgnoreCase.matches("He", "hello").is_some());
+    ///
+    /// let m = Matcher::Fuzzy.matches("fb", "foo_bar").unwrap();
+    /// assert_eq!(m.positions, [0, 4]);
+    /// ```
+    pub fn matches(&self, pattern: &str, candidate: &str) -> Option<Match> {
+        match self {
+            Self::Prefix => prefix(pattern, candidate, false),
+            Self::PrefixIgnoreCase => prefix(pattern, candidate, true),
+            Self::Fuzzy => {
+                let ignore_case = !pattern.chars().any(char::is_uppercase);
+                fuzzy(pattern, candidate, ignore_case)
+            }
+        }
+    }
+
+    /// Matches a pattern against every candidate, returning those that
+    /// matched, best first, along with the positions that matched.
+    ///
+    /// Candidates that score the same are ordered shortest first, then in
+    /// their original order.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::matching::Matcher;
+    /// let found = Matcher::PrefixIgnoreCase.filter("HE", ["help", "world", "Hello"]);
+    /// let texts: Vec<_> = found.iter().map(|c| c.text.as_str()).collect();
+    /// assert_eq!(texts, ["help", "Hello"]);
+    /// ```
+    pub fn filter<I>(&self, pattern: &str, candidates: I) -> Vec<Candidate>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut found: Vec<(i64, Candidate)> = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let text = candidate.into();
+                let m = self.matches(pattern, &text)?;
+                Some((
+                    m.score,
+                    Candidate {
+                        positions: m.positions,
+                        ..Candidate::from(text)
+                    },
+                ))
+            })
+            .collect();
+
+        found.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then(a.text.chars().count().cmp(&b.text.chars().count()))
+        });
+
+        found.into_iter().map(|(_, c)| c).collect()
+    }
+}
+
+fn prefix(pattern: &str, candidate: &str, ignore_case: bool) -> Option<Match> {
+    let mut chars = candidate.chars();
+    let mut len = 0;
+
+    for p in pattern.chars() {
+        let c = chars.next()?;
+        if !eq(p, c, ignore_case) {
+            return None;
+        }
+        len += 1;
+    }
+
+    Some(Match {
+        score: -(chars.count() as i64),
+        positions: (0..len).collect(),
+    })
+}
+
+fn fuzzy(pattern: &str, candidate: &str, ignore_case: bool) -> Option<Match> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = candidate.chars().collect();
+
+    if pattern.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    if pattern.len() > text.len() {
+        return None;
+    }
+
+    let bonuses: Vec<i64> = (0..text.len())
+        .map(|i| bonus(i.checked_sub(1).map(|p| text[p]), text[i]))
+        .collect();
+
+    // `scores[i][j]` is the best score of matching `pattern[..=i]` with
+    // `pattern[i]` landing on `text[j]`; `from[i][j]` is where
+    // `pattern[i - 1]` landed in that match, and `runs[i][j]` the bonus at
+    // the start of its run of consecutive characters.
+    let mut scores = vec![vec![None::<i64>; text.len()]; pattern.len()];
+    let mut from = vec![vec![0; text.len()]; pattern.len()];
+    let mut runs = vec![vec![0; text.len()]; pattern.len()];
+
+    for (i, &p) in pattern.iter().enumerate() {
+        // The best score of the previous row, followed by a gap of at
+        // least one character, along with where it came from.
+        let mut gapped: Option<(i64, usize)> = None;
+
+        for j in i..text.len() {
+            let consecutive = if i == 0 { None } else { scores[i - 1][j - 1] };
+
+            if eq(p, text[j], ignore_case) {
+                let best = if i == 0 {
+                    let score = SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+                    Some((score, 0, bonuses[j]))
+                } else {
+                    // As with fzf, a consecutive character scores at least
+                    // as well as the start of its run, which the start of a
+                    // word within the run takes over.
+                    let consecutive = consecutive.map(|s| {
+                        let mut run = runs[i - 1][j - 1];
+                        if bonuses[j] >= BONUS_DELIMITER {
+                            run = run.max(bonuses[j]);
+                        }
+
+                        let bonus = bonuses[j].max(run).max(BONUS_CONSECUTIVE);
+                        (s + SCORE_MATCH + bonus, j - 1, run)
+                    });
+
+                    let gapped = gapped.map(|(g, k)| (g + SCORE_MATCH + bonuses[j], k, bonuses[j]));
+                    match (consecutive, gapped) {
+                        (Some(c), Some(g)) => Some(if c.0 >= g.0 { c } else { g }),
+                        (c, g) => c.or(g),
+                    }
+                };
+
+                if let Some((score, k, run)) = best {
+                    scores[i][j] = Some(score);
+                    from[i][j] = k;
+                    runs[i][j] = run;
+                }
+            }
+
+            if i != 0 {
+                gapped = match (gapped, consecutive) {
+                    (Some((g, k)), Some(c))
+                        if g + PENALTY_GAP_EXTENSION >= c + PENALTY_GAP_START =>
+                    {
+                        Some((g + PENALTY_GAP_EXTENSION, k))
+                    }
+                    (_, Some(c)) => Some((c + PENALTY_GAP_START, j - 1)),
+                    (Some((g, k)), None) => Some((g + PENALTY_GAP_EXTENSION, k)),
+                    (None, None) => None,
+                };
+            }
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, s)| s.map(|s| (j, s)))
+        .max_by_key(|&(j, s)| (s, std::cmp::Reverse(j)))?;
+
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some(Match { score, positions })
+}
+
+/// The bonus for matching `c`, given the character before it.
+fn bonus(prev: Option<char>, c: char) -> i64 {
+    match prev {
+        None => BONUS_WHITESPACE,
+        Some(p) if p.is_whitespace() => BONUS_WHITESPACE,
+        Some(p) if DELIMITERS.contains(p) => BONUS_DELIMITER,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_ascii_digit() && c.is_ascii_digit() => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+fn eq(a: char, b: char, ignore_case: bool) -> bool {
+    if ignore_case {
+        a.to_lowercase().eq(b.to_lowercase())
+    } else {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked<const N: usize>(pattern: &str, candidates: [&str; N]) -> Vec<String> {
+        Matcher::Fuzzy
+            .filter(pattern, candidates)
+            .into_iter()
+            .map(|c| c.text)
+            .collect()
+    }
+
+    #[test]
+    fn consecutive_beats_word_starts_after_gaps() {
+        assert_eq!(ranked("ma", ["my_app", "make"]), ["make", "my_app"]);
+        assert_eq!(ranked("ab", ["a_b", "ab"]), ["ab", "a_b"]);
+        assert_eq!(ranked("foo", ["f_o_o", "foo"]), ["foo", "f_o_o"]);
+    }
+
+    #[test]
+    fn word_starts_beat_the_middle_of_words() {
+        assert_eq!(ranked("fb", ["afbx", "foo_bar"]), ["foo_bar", "afbx"]);
+        assert_eq!(ranked("gb", ["gabby", "getBuffer"]), ["getBuffer", "gabby"]);
+        assert_eq!(ranked("bar", ["foobar", "foo bar"]), ["foo bar", "foobar"]);
+    }
+
+    #[test]
+    fn scores_runs_from_their_start() {
+        let run = Matcher::Fuzzy.matches("ab", "ab").unwrap();
+        let gapped = Matcher::Fuzzy.matches("ab", "a_b").unwrap();
+        assert_eq!(run.score, 62);
+        assert_eq!(gapped.score, 58);
+
+        // The rest of a run at the start of a word scores like its start.
+        let m = Matcher::Fuzzy.matches("bar", "foo_bar").unwrap();
+        assert_eq!(m.positions, [4, 5, 6]);
+        assert_eq!(m.score, 3 * SCORE_MATCH + 4 * BONUS_DELIMITER);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(Matcher::Fuzzy.matches("fb", "FooBar").is_some());
+        assert!(Matcher::Fuzzy.matches("Fb", "fooBar").is_none());
+    }
+}
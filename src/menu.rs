@@ -0,0 +1,503 @@
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::highlight::{self, Span};
+use crate::render::{self, Line};
+use crate::{Candidate, Completion, Style, Theme};
+
+/// How often the spinner advances while completing in the background.
+pub(crate) const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The frames of the spinner shown while completing in the background.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// The completion menu, drawn beneath the input.
+#[derive(Debug, Default)]
+pub(crate) struct Menu {
+    /// The current completion candidates.
+    pub completions: Vec<Candidate>,
+    /// The index of the selected candidate.
+    pub index: usize,
+    /// The word being cycled through inline, if any.
+    pub cycle: Option<Cycle>,
+    /// The completion running in the background, if any.
+    pending: Option<Pending>,
+    /// The thread running background completions, once one was started.
+    worker: Option<Worker>,
+    /// Counts the background completions started, to tell their results
+    /// apart.
+    generation: u64,
+    /// The current frame of the spinner.
+    frame: usize,
+    /// The styles to draw the menu with.
+    pub theme: Theme,
+}
+
+/// The word being cycled through inline, as in zsh's menu-complete.
+#[derive(Debug)]
+pub(crate) struct Cycle {
+    /// The char index the word starts at.
+    pub start: usize,
+    /// The quote the word was opened with.
+    pub quote: Option<char>,
+    /// The word as it was before cycling, to revert to.
+    pub original: String,
+}
+
+/// A line of the menu.
+enum Row<'a> {
+    /// The name of a group of candidates.
+    Header(&'a str),
+    /// The indices of the candidates on the line.
+    Items(Range<usize>),
+}
+
+/// A completion running in the background.
+#[derive(Debug)]
+struct Pending {
+    /// The generation of the completion.
+    generation: u64,
+    /// The input and cursor the completion was started with.
+    data: String,
+    cursor: usize,
+}
+
+/// A completion for the worker to run.
+struct Job {
+    generation: u64,
+    completion: Arc<dyn Completion + Send + Sync>,
+    data: String,
+    start: usize,
+    cursor: usize,
+}
+
+/// The thread running background completions, one at a time.
+///
+/// Jobs queued up while one is running are stale by the time it finishes,
+/// so only the latest is run.
+#[derive(Debug)]
+struct Worker {
+    jobs: Sender<Job>,
+    results: Receiver<(u64, Vec<Candidate>)>,
+}
+
+impl Worker {
+    fn spawn() -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (sender, results) = mpsc::channel();
+
+        // Stops once the menu is dropped, closing the queue.
+        thread::spawn(move || {
+            while let Ok(mut job) = queue.recv() {
+                while let Ok(next) = queue.try_recv() {
+                    job = next;
+                }
+
+                let found = job.completion.complete(&job.data, job.start, job.cursor);
+                if sender.send((job.generation, found)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { jobs, results }
+    }
+}
+
+impl Menu {
+    /// Creates a closed menu drawn with `theme`.
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            theme,
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether the menu is open, with candidates or loading them.
+    pub fn is_open(&self) -> bool {
+        !self.completions.is_empty() || self.is_loading()
+    }
+
+    /// Returns whether a completion is running in the background.
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Replaces the candidates, resetting the selection and cancelling any
+    /// background completion or cycling.
+    ///
+    /// Candidates are gathered into their groups, in order of each group's
+    /// first candidate, keeping their order within the group.
+    pub fn set(&mut self, completions: Vec<Candidate>) {
+        let mut groups: Vec<Vec<Candidate>> = Vec::new();
+        for c in completions {
+            match groups.iter_mut().find(|g| g[0].group == c.group) {
+                Some(group) => group.push(c),
+                None => groups.push(vec![c]),
+            }
+        }
+
+        self.completions = groups.into_iter().flatten().collect();
+        self.index = 0;
+        self.cycle = None;
+        self.pending = None;
+    }
+
+    /// Starts running a completion in the background, replacing any
+    /// previous one. Until it finishes, the menu shows a spinner.
+    ///
+    /// Completions run one at a time on the menu's worker thread: the
+    /// results of a previous one still running are discarded once it ends.
+    pub fn load(
+        &mut self,
+        completion: Arc<dyn Completion + Send + Sync>,
+        data: &str,
+        start: usize,
+        cursor: usize,
+    ) {
+        self.set(Vec::new());
+        self.generation += 1;
+
+        let job = Job {
+            generation: self.generation,
+            completion,
+            data: data.to_string(),
+            start,
+            cursor,
+        };
+
+        let worker = self.worker.get_or_insert_with(Worker::spawn);
+        if let Err(mpsc::SendError(job)) = worker.jobs.send(job) {
+            // The worker stopped, as a completion panicked; start another.
+            let worker = self.worker.insert(Worker::spawn());
+            let _ = worker.jobs.send(job);
+        }
+
+        self.pending = Some(Pending {
+            generation: self.generation,
+            data: data.to_string(),
+            cursor,
+        });
+    }
+
+    /// Checks on the background completion, taking its candidates if it
+    /// finished, or advancing the spinner if not. Discards the candidates
+    /// if the input changed since it started.
+    ///
+    /// Returns whether any candidates were taken.
+    pub fn poll(&mut self, data: &str, cursor: usize) -> bool {
+        let Some(pending) = &self.pending else {
+            return false;
+        };
+
+        let Some(worker) = &self.worker else {
+            return false;
+        };
+
+        // Results of earlier completions are skipped, as they're stale.
+        loop {
+            match worker.results.try_recv() {
+                Ok((generation, completions)) if generation == pending.generation => {
+                    let current = pending.data == data && pending.cursor == cursor;
+                    self.set(if current { completions } else { Vec::new() });
+                    return !self.completions.is_empty();
+                }
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.worker = None;
+                    self.set(Vec::new());
+                    return false;
+                }
+            }
+        }
+
+        self.frame = (self.frame + 1) % SPINNER.len();
+        false
+    }
+
+    /// Moves the selection by `delta`, staying within the candidates.
+    pub fn select(&mut self, delta: isize) {
+        self.index = self
+            .index
+            .saturating_add_signed(delta)
+            .min(self.completions.len().saturating_sub(1));
+    }
+
+    /// Moves the selection by `delta` lines, staying in the same column
+    /// where possible and skipping over group headers.
+    pub fn select_row(&mut self, delta: isize) {
+        let rows: Vec<Range<usize>> = self
+            .rows()
+            .into_iter()
+            .filter_map(|row| match row {
+                Row::Header(_) => None,
+                Row::Items(items) => Some(items),
+            })
+            .collect();
+
+        let Some(current) = rows.iter().position(|r| r.contains(&self.index)) else {
+            return;
+        };
+
+        let column = self.index - rows[current].start;
+        let row = &rows[current.saturating_add_signed(delta).min(rows.len() - 1)];
+        self.index = (row.start + column).min(row.end - 1);
+    }
+
+    /// Moves the selection by `delta`, wrapping around the candidates.
+    pub fn select_wrapping(&mut self, delta: isize) {
+        let len = self.completions.len() as isize;
+        if len != 0 {
+            self.index = (self.index as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+
+    /// Returns the selected candidate, if any.
+    pub fn selected(&self) -> Option<&Candidate> {
+        self.completions.get(self.index)
+    }
+
+    /// Forgets the candidates, cancelling any background completion.
+    pub fn close(&mut self) {
+        self.set(Vec::new());
+    }
+
+    /// Lays out the menu as lines to draw beneath the input: nothing if
+    /// there are no candidates, or a spinner if they're still loading.
+    pub fn lines(&self) -> Vec<Line> {
+        if self.is_loading() {
+            let spinner = SPINNER[self.frame].to_string();
+            return vec![render::styled(&format!(" {spinner}"), self.theme.menu)];
+        }
+
+        let rows = self.rows();
+
+        let mut width = 0;
+        for row in &rows {
+            if let Row::Items(items) = row {
+                let len: usize = self.completions[items.clone()]
+                    .iter()
+                    .map(|c| c.text.chars().count())
+                    .sum();
+
+                width = width.max(len);
+            }
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                let mut line = render::styled(" ", Style::default());
+
+                match row {
+                    Row::Header(group) => {
+                        line.extend(render::styled(group, self.theme.menu_header));
+                    }
+                    Row::Items(items) => {
+                        let idx = items.start;
+                        let c = &self.completions[items];
+
+                        line.extend(self.candidate(&c[0], idx));
+
+                        if let Some(r) = c.get(1) {
+                            let padding =
+                                width - c[0].text.chars().count() - r.text.chars().count();
+                            let padding = " ".repeat(padding + 1);
+                            line.extend(render::styled(&padding, Style::default()));
+                            line.extend(self.candidate(r, idx + 1));
+                        }
+                    }
+                }
+
+                line
+            })
+            .collect()
+    }
+
+    /// Lays out the candidates in lines of two, with each group under its
+    /// header.
+    fn rows(&self) -> Vec<Row<'_>> {
+        let mut rows = Vec::new();
+
+        let mut start = 0;
+        while let Some(first) = self.completions.get(start) {
+            let end = start
+                + self.completions[start..]
+                    .iter()
+                    .take_while(|c| c.group == first.group)
+                    .count();
+
+            if let Some(group) = &first.group {
+                rows.push(Row::Header(group));
+            }
+
+            for i in (start..end).step_by(2) {
+                rows.push(Row::Items(i..end.min(i + 2)));
+            }
+
+            start = end;
+        }
+
+        rows
+    }
+
+    /// Lays out a single candidate, highlighting the matched characters.
+    fn candidate(&self, candidate: &Candidate, idx: usize) -> Line {
+        let text = &candidate.text;
+        let style = if idx == self.index {
+            self.theme.menu_selected
+        } else {
+            self.theme.menu
+        };
+
+        let mut spans = vec![Span::new(0..text.len(), style)];
+        spans.extend(
+            text.char_indices()
+                .enumerate()
+                .filter(|(i, _)| candidate.positions.contains(i))
+                .map(|(_, (b, ch))| Span::new(b..b + ch.len_utf8(), self.theme.menu_matched)),
+        );
+
+        highlight::render(text, &spans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(candidates: &[(&str, Option<&str>)]) -> Menu {
+        let mut menu = Menu::default();
+        menu.set(
+            candidates
+                .iter()
+                .map(|&(text, group)| Candidate {
+                    group: group.map(str::to_string),
+                    ..Candidate::from(text)
+                })
+                .collect(),
+        );
+
+        menu
+    }
+
+    fn texts(menu: &Menu) -> Vec<&str> {
+        menu.completions.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn set_gathers_groups() {
+        let menu = menu(&[
+            ("a", Some("one")),
+            ("b", Some("two")),
+            ("c", Some("one")),
+            ("d", None),
+            ("e", Some("two")),
+        ]);
+
+        assert_eq!(texts(&menu), ["a", "c", "b", "e", "d"]);
+        assert_eq!(menu.index, 0);
+    }
+
+    #[test]
+    fn lines_show_headers() {
+        let menu = menu(&[
+            ("a", Some("one")),
+            ("b", Some("one")),
+            ("c", Some("one")),
+            ("d", Some("two")),
+        ]);
+
+        let lines: Vec<String> = menu
+            .lines()
+            .iter()
+            .map(|line| line.iter().map(|cell| cell.text.as_str()).collect())
+            .collect();
+
+        assert_eq!(lines, [" one", " a b", " c", " two", " d"]);
+    }
+
+    #[test]
+    fn select_row_skips_headers() {
+        // Rows: `one`, `a b`, `c`, `two`, `d e`.
+        let mut menu = menu(&[
+            ("a", Some("one")),
+            ("b", Some("one")),
+            ("c", Some("one")),
+            ("d", Some("two")),
+            ("e", Some("two")),
+        ]);
+
+        menu.index = 1;
+        menu.select_row(1);
+        assert_eq!(menu.index, 2);
+
+        // Over the header of `two`, back to the first column.
+        menu.select_row(1);
+        assert_eq!(menu.index, 3);
+
+        // Stays on the last row.
+        menu.select_row(1);
+        assert_eq!(menu.index, 3);
+
+        menu.index = 4;
+        menu.select_row(-2);
+        assert_eq!(menu.index, 1);
+
+        // Stays on the first row.
+        menu.select_row(-1);
+        assert_eq!(menu.index, 1);
+    }
+
+    /// Polls the background completion until it finishes, returning what
+    /// the last poll did.
+    fn finish_loading(menu: &mut Menu, data: &str, cursor: usize) -> bool {
+        loop {
+            let took = menu.poll(data, cursor);
+            if !menu.is_loading() {
+                return took;
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn poll_takes_candidates() {
+        let mut menu = Menu::default();
+        menu.load(
+            Arc::new(|_: &str, _: usize, _: usize| vec!["ab".into()]),
+            "a",
+            0,
+            1,
+        );
+
+        assert!(finish_loading(&mut menu, "a", 1));
+        assert_eq!(texts(&menu), ["ab"]);
+    }
+
+    #[test]
+    fn poll_without_candidates() {
+        let mut menu = Menu::default();
+        menu.load(
+            Arc::new(|_: &str, _: usize, _: usize| Vec::new()),
+            "a",
+            0,
+            1,
+        );
+        assert!(!finish_loading(&mut menu, "a", 1));
+
+        // Stale candidates aren't taken either.
+        menu.load(
+            Arc::new(|_: &str, _: usize, _: usize| vec!["ab".into()]),
+            "a",
+            0,
+            1,
+        );
+        assert!(!finish_loading(&mut menu, "ab", 2));
+        assert!(menu.completions.is_empty());
+    }
+}
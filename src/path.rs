@@ -0,0 +1,89 @@
+//! Completion of filesystem paths.
+//!
+//! Example:
+//! ```
+//! # use linoleum::{path, Editor};
+//! let editor = Editor::new(" > ").completion(path::complete);
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{words, Candidate};
+
+/// Completes the filesystem path before the cursor.
+///
+/// Handles relative and absolute paths, as well as a leading `~` for the
+/// home directory. Hidden files are only offered once the name being
+/// completed starts with a `.`. Directories are completed with a trailing
+/// `/`, so that completing again descends into them.
+///
+/// Quotes and escapes in the typed path are understood as described in
+/// [`words`].
+pub fn complete(data: &str, start: usize, end: usize) -> Vec<Candidate> {
+    let word = words::word_in(data, start, end);
+
+    if word == "~" {
+        return vec!["~/".into()];
+    }
+
+    let (dir, name) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word.as_str()),
+    };
+
+    let Some(path) = expand(dir) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<(String, bool)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            if !file_name.starts_with(name)
+                || (file_name.starts_with('.') && !name.starts_with('.'))
+            {
+                return None;
+            }
+
+            // Follows symlinks, so that links to directories count too.
+            let is_dir = fs::metadata(entry.path()).is_ok_and(|m| m.is_dir());
+            Some((file_name, is_dir))
+        })
+        .collect();
+
+    found.sort();
+
+    let matched = word.chars().count();
+    found
+        .into_iter()
+        .map(|(file_name, is_dir)| {
+            let mut text = format!("{dir}{file_name}");
+            if is_dir {
+                text.push('/');
+            }
+
+            Candidate {
+                positions: (0..matched).collect(),
+                ..Candidate::from(text)
+            }
+        })
+        .collect()
+}
+
+/// Expands a leading `~` and turns an empty directory into the current
+/// one. Returns `None` if the home directory is unknown.
+fn expand(dir: &str) -> Option<PathBuf> {
+    if dir.is_empty() {
+        Some(PathBuf::from("."))
+    } else if let Some(rest) = dir.strip_prefix("~/") {
+        Some(PathBuf::from(env::var_os("HOME")?).join(rest))
+    } else {
+        Some(PathBuf::from(dir))
+    }
+}
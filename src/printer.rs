@@ -0,0 +1,97 @@
+use std::fmt::Display;
+use std::io::{self, stdout, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often to check for text to print while reading.
+pub(crate) const PRINT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A handle to print with without breaking the editor, or to have it
+/// refresh its prompt, from any thread.
+///
+/// While the editor is reading, printed text goes above the prompt, and
+/// the prompt and input are drawn again below it. Otherwise, it's printed
+/// right away. Get one with [`Editor::printer`](crate::Editor::printer).
+///
+/// Example:
+/// ```no_run
+/// # use std::{thread, time::Duration};
+/// # use linoleum::Editor;
+/// let mut editor = Editor::new(" > ");
+/// let printer = editor.printer();
+///
+/// thread::spawn(move || loop {
+///     thread::sleep(Duration::from_secs(1));
+///     printer.print("tick").unwrap();
+/// });
+///
+/// editor.read().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Printer {
+    queue: Arc<Mutex<Queue>>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    /// Whether the editor is reading, and will print the text itself.
+    reading: bool,
+    text: String,
+    /// Whether the editor should evaluate its prompt again.
+    refresh: bool,
+}
+
+impl Printer {
+    /// Prints `text` on its own lines.
+    pub fn print<S: Display>(&self, text: S) -> io::Result<()> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.reading {
+            queue.text.push_str(&text.to_string());
+            queue.text.push('\n');
+            Ok(())
+        } else {
+            writeln!(stdout(), "{text}")
+        }
+    }
+
+    /// Has the editor evaluate its prompt again and draw it, keeping the
+    /// input as is, if it's reading.
+    pub fn refresh_prompt(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.refresh = queue.reading;
+    }
+
+    /// Holds on to printed text until the returned guard is dropped.
+    pub(crate) fn start(&self) -> Reading {
+        self.queue.lock().unwrap().reading = true;
+        Reading(self.clone())
+    }
+
+    /// Takes the text printed since the last call, with newlines suitable
+    /// for raw mode.
+    pub(crate) fn take(&self) -> String {
+        let text = std::mem::take(&mut self.queue.lock().unwrap().text);
+        text.replace('\n', "\r\n")
+    }
+
+    /// Returns whether the prompt should be refreshed since the last call.
+    pub(crate) fn take_refresh(&self) -> bool {
+        std::mem::take(&mut self.queue.lock().unwrap().refresh)
+    }
+}
+
+/// Prints any text left once dropped, and has the printer print right
+/// away from then on.
+pub(crate) struct Reading(Printer);
+
+impl Drop for Reading {
+    fn drop(&mut self) {
+        let mut queue = self.0.queue.lock().unwrap();
+        queue.reading = false;
+        queue.refresh = false;
+
+        let mut stdout = stdout();
+        let _ = stdout.write_all(std::mem::take(&mut queue.text).as_bytes());
+        let _ = stdout.flush();
+    }
+}
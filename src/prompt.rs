@@ -0,0 +1,51 @@
+use std::fmt::Display;
+
+use crate::State;
+
+/// A prompt, drawn before the user input.
+///
+/// Returns the text to show, given the state of the editor. It may span
+/// several lines and contain escape sequences; see
+/// [`Editor::new`](crate::Editor::new). The prompt is evaluated again
+/// whenever the state changes, and whenever it's refreshed; see
+/// [`Editor::prompt_refresh`](crate::Editor::prompt_refresh).
+///
+/// Implemented for anything that implements [`Display`], regardless of the
+/// state; those are only evaluated when reading starts and when refreshed.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Editor, Prompt, State};
+/// // Shows where in the history the input is from.
+/// struct HistoryPrompt;
+///
+/// impl Prompt for HistoryPrompt {
+///     fn render(&self, state: &State) -> String {
+///         match state.history_index {
+///             Some(i) => format!("!{} > ", i + 1),
+///             None => format!("[{}] > ", state.line + 1),
+///         }
+///     }
+/// }
+///
+/// let editor = Editor::new(HistoryPrompt);
+/// ```
+pub trait Prompt {
+    fn render(&self, state: &State) -> String;
+
+    /// Whether the prompt depends on the state, and should be evaluated
+    /// again whenever it changes.
+    fn uses_state(&self) -> bool {
+        true
+    }
+}
+
+impl<T: Display + ?Sized> Prompt for T {
+    fn render(&self, _state: &State) -> String {
+        self.to_string()
+    }
+
+    fn uses_state(&self) -> bool {
+        false
+    }
+}
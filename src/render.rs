@@ -0,0 +1,592 @@
+use std::io::{self, Write};
+
+use crossterm::{cursor, queue, terminal};
+
+use crate::Style;
+
+/// A single character on screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cell {
+    /// The character, along with any zero-width text before it, such as
+    /// the escape sequences of a prompt.
+    pub text: String,
+    pub style: Style,
+    /// The zero-width text of the line up to and including the cell, to
+    /// write again when drawing starts after it.
+    pub escapes: String,
+}
+
+/// A line of cells, which wraps onto as many rows as it needs.
+pub(crate) type Line = Vec<Cell>;
+
+/// Turns plain text into a line of cells with the same style.
+pub(crate) fn styled(text: &str, style: Style) -> Line {
+    text.chars()
+        .map(|ch| Cell {
+            text: ch.to_string(),
+            style,
+            escapes: String::new(),
+        })
+        .collect()
+}
+
+/// Adds `cells` to the end of `line`, after its zero-width text.
+pub(crate) fn extend(line: &mut Line, cells: impl IntoIterator<Item = Cell>) {
+    let before = line.last().map(|c| c.escapes.clone()).unwrap_or_default();
+    line.extend(cells.into_iter().map(|mut cell| {
+        cell.escapes.insert_str(0, &before);
+        cell
+    }));
+}
+
+/// Turns text containing escape sequences, such as a prompt, into lines
+/// of cells, split on newlines. See [`raw`].
+pub(crate) fn lines(text: &str) -> Vec<Line> {
+    text.split('\n')
+        .map(|line| raw(line.strip_suffix('\r').unwrap_or(line)))
+        .collect()
+}
+
+/// Turns text containing escape sequences, such as a prompt, into a line
+/// of cells. The escape sequences take up no space, and are kept with the
+/// character after them, or the last one if there is none.
+///
+/// Anything between `\x01` and `\x02` also takes up no space, as with
+/// readline. The markers themselves are dropped.
+pub(crate) fn raw(text: &str) -> Line {
+    let mut line: Line = Vec::new();
+    let mut pending = String::new();
+    let mut escapes = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\x01' {
+            pending.extend(chars.by_ref().take_while(|&ch| ch != '\x02'));
+            continue;
+        }
+
+        if ch == '\x02' {
+            continue;
+        }
+
+        if ch != '\x1b' {
+            escapes.push_str(&pending);
+            pending.push(ch);
+            line.push(Cell {
+                text: std::mem::take(&mut pending),
+                style: Style::default(),
+                escapes: escapes.clone(),
+            });
+            continue;
+        }
+
+        pending.push(ch);
+        match chars.next() {
+            // CSI sequences end with a character in `@..=~`.
+            Some('[') => {
+                pending.push('[');
+                for ch in chars.by_ref() {
+                    pending.push(ch);
+                    if ('@'..='~').contains(&ch) {
+                        break;
+                    }
+                }
+            }
+            // OSC sequences end with BEL or ST.
+            Some(']') => {
+                pending.push(']');
+                while let Some(ch) = chars.next() {
+                    pending.push(ch);
+                    if ch == '\x07' {
+                        break;
+                    } else if ch == '\x1b' && chars.peek() == Some(&'\\') {
+                        pending.push(chars.next().unwrap());
+                        break;
+                    }
+                }
+            }
+            Some(ch) => pending.push(ch),
+            None => {}
+        }
+    }
+
+    match line.last_mut() {
+        Some(last) => {
+            last.text.push_str(&pending);
+            last.escapes.push_str(&pending);
+        }
+        None if !pending.is_empty() => line.push(Cell {
+            text: pending.clone(),
+            style: Style::default(),
+            escapes: pending,
+        }),
+        None => {}
+    }
+
+    line
+}
+
+/// Everything to draw, and where to put the cursor, as a line and an
+/// index into it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Frame {
+    pub lines: Vec<Line>,
+    pub cursor: (usize, usize),
+    /// Drawn right-aligned on the line of the cursor, as long as it fits
+    /// on the same row.
+    pub right: Line,
+}
+
+/// Draws frames, only writing what changed since the last one.
+#[derive(Debug, Default)]
+pub(crate) struct Renderer {
+    /// The rows drawn last, after wrapping.
+    rows: Vec<Line>,
+    /// Whether each row on screen is joined to the one above by the
+    /// terminal wrapping onto it, rather than by a newline. Terminals that
+    /// wrap the screen again on resize join those rows back together.
+    soft: Vec<bool>,
+    /// The number of rows the editor has taken up on screen, which may be
+    /// more than were drawn last.
+    height: usize,
+    /// The position of the terminal cursor, relative to the first row.
+    /// The column is `None` if unknown.
+    row: usize,
+    col: Option<usize>,
+    /// The width of the terminal, which rows are wrapped at.
+    width: usize,
+}
+
+impl Renderer {
+    /// Creates a renderer for a terminal `width` columns wide.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Draws `frame` over the last one.
+    ///
+    /// Rows continuing a line are written by letting the terminal wrap
+    /// onto them where possible, so that it knows they belong together.
+    pub fn render(&mut self, stdout: &mut impl Write, frame: &Frame) -> io::Result<()> {
+        let (rows, continued, target) = wrap(frame, self.width);
+
+        let mut out = Vec::new();
+        let old_rows = std::mem::take(&mut self.rows);
+        let old_soft = std::mem::take(&mut self.soft);
+        let mut soft = Vec::with_capacity(rows.len());
+
+        for r in 0..rows.len().max(old_rows.len()) {
+            let old = old_rows.get(r).map_or(&[][..], Vec::as_slice);
+            let new = rows.get(r).map_or(&[][..], Vec::as_slice);
+            if r < rows.len() {
+                soft.push(old_soft.get(r).copied().unwrap_or(false));
+            }
+
+            let Some(start) = (0..old.len().max(new.len())).find(|&i| old.get(i) != new.get(i))
+            else {
+                continue;
+            };
+
+            // The first change is never past the end of the new row.
+            let end = if new.len() < old.len() {
+                new.len()
+            } else {
+                (0..new.len()).rfind(|&i| old.get(i) != new.get(i)).unwrap() + 1
+            };
+
+            if start == 0 && !new.is_empty() && continued[r] {
+                // Write the last cell of the row above again, unless it was
+                // just written, so that the terminal wraps onto this one.
+                if self.row + 1 != r || self.col != Some(self.width) {
+                    let (above, last) = (&rows[r - 1], self.width - 1);
+                    self.move_to(&mut out, r - 1, last)?;
+                    write_cells(&mut out, escapes_before(above, last), &above[last..])?;
+                }
+
+                self.row = r;
+                self.height = self.height.max(r + 1);
+                soft[r] = true;
+            } else {
+                self.move_to(&mut out, r, start)?;
+            }
+
+            // Zero-width text before the change, such as a prompt's colors,
+            // may set up how the cells after it look.
+            let before = if start > 0 {
+                escapes_before(new, start)
+            } else if continued[r] {
+                rows[r - 1].last().map_or("", |c| c.escapes.as_str())
+            } else {
+                ""
+            };
+
+            write_cells(&mut out, before, &new[start..end])?;
+            self.col = Some(end);
+
+            if new.len() < old.len() {
+                queue!(out, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+            }
+        }
+
+        self.move_to(&mut out, target.0, target.1)?;
+        self.rows = rows;
+        self.soft = soft;
+
+        stdout.write_all(&out)?;
+        stdout.flush()
+    }
+
+    /// Clears everything drawn at the old width, so that the next frame is
+    /// drawn from scratch at `width`.
+    ///
+    /// Most terminals wrap what's on screen again when resized, so the rows
+    /// drawn before may take up more rows now, with the terminal cursor
+    /// further down.
+    pub fn resize(&mut self, stdout: &mut impl Write, width: usize) -> io::Result<()> {
+        let width = width.max(1);
+        if width == self.width {
+            return Ok(());
+        }
+
+        // The lines on screen, as the rows each starts at and their length,
+        // and where in its line the cursor is.
+        let mut lines: Vec<(usize, usize)> = Vec::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            match lines.last_mut() {
+                Some((_, len)) if self.soft[r] => *len += row.len(),
+                _ => lines.push((r, row.len())),
+            }
+        }
+
+        let line = lines.iter().rposition(|&(r, _)| r <= self.row).unwrap_or(0);
+        let first = lines.get(line).map_or(self.row, |&(r, _)| r);
+        let col = self.col.unwrap_or(0).min(self.width - 1);
+        let offset = (self.row - first) * self.width + col;
+
+        self.row = lines[..line]
+            .iter()
+            .map(|&(_, len)| len.max(1).div_ceil(width))
+            .sum::<usize>()
+            + offset / width;
+        self.height = self.height.max(self.row + 1);
+        self.width = width;
+
+        self.clear(stdout)?;
+        stdout.flush()
+    }
+
+    /// Clears everything drawn, leaving the terminal cursor where the
+    /// first row was, so the next frame is drawn from scratch.
+    pub fn clear(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        self.col = None;
+        self.move_to(&mut buf, 0, 0)?;
+        queue!(buf, terminal::Clear(terminal::ClearType::FromCursorDown))?;
+        out.write_all(&buf)?;
+
+        self.rows.clear();
+        self.soft.clear();
+        self.height = 1;
+        Ok(())
+    }
+
+    /// Moves below everything drawn, onto a fresh line, and forgets it.
+    pub fn leave(&mut self, stdout: &mut impl Write) -> io::Result<()> {
+        let mut out = Vec::new();
+        self.move_to(&mut out, self.rows.len().saturating_sub(1), 0)?;
+        write!(out, "\r\n")?;
+
+        *self = Self::new(self.width);
+        stdout.write_all(&out)?;
+        stdout.flush()
+    }
+
+    /// Moves the terminal cursor, creating rows below the ones taken up so
+    /// far as needed.
+    fn move_to(&mut self, out: &mut Vec<u8>, row: usize, col: usize) -> io::Result<()> {
+        if row > self.row {
+            let last = self.height.saturating_sub(1).max(self.row);
+            let down = row.min(last) - self.row;
+            if down != 0 {
+                queue!(out, cursor::MoveDown(down as u16))?;
+            }
+
+            for _ in last..row {
+                write!(out, "\r\n")?;
+                self.col = Some(0);
+            }
+        } else if row < self.row {
+            queue!(out, cursor::MoveUp((self.row - row) as u16))?;
+        }
+
+        self.row = row;
+        self.height = self.height.max(row + 1);
+
+        if self.col != Some(col) {
+            queue!(out, cursor::MoveToColumn(col as u16))?;
+            self.col = Some(col);
+        }
+
+        Ok(())
+    }
+}
+
+/// Wraps the lines of a frame into rows of `width` cells, and finds the
+/// row and column of the cursor. Also returns whether each row continues
+/// the line of the one above.
+fn wrap(frame: &Frame, width: usize) -> (Vec<Line>, Vec<bool>, (usize, usize)) {
+    let mut rows = Vec::new();
+    let mut continued = Vec::new();
+    let mut target = (0, 0);
+
+    for (i, line) in frame.lines.iter().enumerate() {
+        if i == frame.cursor.0 {
+            target = (rows.len() + frame.cursor.1 / width, frame.cursor.1 % width);
+        }
+
+        let first = rows.len();
+        let mut chunks = line.chunks(width).map(<[Cell]>::to_vec).peekable();
+        if chunks.peek().is_none() {
+            rows.push(Vec::new());
+        }
+        rows.extend(chunks);
+        continued.extend((first..rows.len()).map(|r| r != first));
+
+        // Leave at least a column between the line and the right side.
+        let gap = width.saturating_sub(line.len() + frame.right.len());
+        if i == frame.cursor.0 && !frame.right.is_empty() && line.len() < width && gap > 0 {
+            let row = rows.last_mut().unwrap();
+            extend(row, styled(&" ".repeat(gap), Style::default()));
+            row.extend(frame.right.iter().cloned());
+        }
+
+        // A cursor just past a full row sits at the start of the next one.
+        if i == frame.cursor.0 && target.0 == rows.len() {
+            rows.push(Vec::new());
+            continued.push(true);
+        }
+    }
+
+    (rows, continued, target)
+}
+
+/// The zero-width text of `row` before the cell at `i`.
+fn escapes_before(row: &[Cell], i: usize) -> &str {
+    i.checked_sub(1).map_or("", |i| row[i].escapes.as_str())
+}
+
+/// Writes cells, switching styles only where they change, after the
+/// zero-width text `before` them.
+fn write_cells(out: &mut Vec<u8>, before: &str, cells: &[Cell]) -> io::Result<()> {
+    let mut current = None;
+    for cell in cells {
+        if current != Some(cell.style) {
+            write!(out, "\x1b[0m{}", cell.style)?;
+            if current.is_none() {
+                write!(out, "{before}")?;
+            }
+            current = Some(cell.style);
+        }
+
+        write!(out, "{}", cell.text)?;
+    }
+
+    if current.is_some() {
+        write!(out, "\x1b[0m")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders `frame` into a fresh buffer.
+    fn render(renderer: &mut Renderer, frame: &Frame) -> String {
+        let mut out = Vec::new();
+        renderer.render(&mut out, frame).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn frame(lines: &[&str], cursor: (usize, usize)) -> Frame {
+        Frame {
+            lines: lines.iter().map(|line| raw(line)).collect(),
+            cursor,
+            right: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn unchanged_frame_only_moves_cursor() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abc"], (0, 5)));
+
+        assert_eq!(render(&mut renderer, &frame(&["> abc"], (0, 5))), "");
+        assert_eq!(render(&mut renderer, &frame(&["> abc"], (0, 2))), "\x1b[3G");
+    }
+
+    #[test]
+    fn single_cell_edit() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abc"], (0, 5)));
+
+        assert_eq!(
+            render(&mut renderer, &frame(&["> axc"], (0, 5))),
+            "\x1b[4G\x1b[0mx\x1b[0m\x1b[6G"
+        );
+    }
+
+    #[test]
+    fn edit_after_colored_prompt() {
+        let frame = |input: &str| {
+            let mut line = raw("\x1b[32m> ");
+            extend(&mut line, styled(input, Style::default()));
+            Frame {
+                lines: vec![line],
+                cursor: (0, 2 + input.len()),
+                right: Vec::new(),
+            }
+        };
+
+        let mut renderer = Renderer::new(20);
+        render(&mut renderer, &frame("ab"));
+
+        // Only the new cell is written, in the prompt's color.
+        assert_eq!(
+            render(&mut renderer, &frame("abc")),
+            "\x1b[0m\x1b[32mc\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn shrinking_row_clears() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abc"], (0, 5)));
+
+        assert_eq!(
+            render(&mut renderer, &frame(&["> a"], (0, 3))),
+            "\x1b[4G\x1b[K"
+        );
+    }
+
+    #[test]
+    fn cursor_at_width() {
+        // The cursor goes at the start of a new row, rather than past the
+        // edge of the terminal.
+        let mut renderer = Renderer::new(5);
+        assert_eq!(
+            render(&mut renderer, &frame(&["> abc"], (0, 5))),
+            "\x1b[1G\x1b[0m> abc\x1b[0m\r\n"
+        );
+
+        assert_eq!(
+            render(&mut renderer, &frame(&["> ab"], (0, 4))),
+            "\x1b[1A\x1b[5G\x1b[K"
+        );
+    }
+
+    #[test]
+    fn raw_escapes() {
+        let line = raw("\x1b]0;title\x07> ");
+        assert_eq!(line.len(), 2);
+        assert_eq!(line[0].text, "\x1b]0;title\x07>");
+
+        let line = raw("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\");
+        assert_eq!(line.len(), 4);
+        assert_eq!(line[0].text, "\x1b]8;;https://example.com\x1b\\l");
+        assert_eq!(line[3].text, "k\x1b]8;;\x1b\\");
+
+        let line = raw("\x01\x1b[31m\x02a\x01\x1b[0m\x02");
+        assert_eq!(line.len(), 1);
+        assert_eq!(line[0].text, "\x1b[31ma\x1b[0m");
+
+        // Each cell knows the zero-width text up to it, as well as after
+        // it for the last one.
+        let line = raw("\x1b[31m> \x1b[0m");
+        assert_eq!(line[0].escapes, "\x1b[31m");
+        assert_eq!(line[1].escapes, "\x1b[31m\x1b[0m");
+
+        // Text between the markers takes up no space, escape or not.
+        assert_eq!(raw("\x01abc\x02> ").len(), 2);
+    }
+
+    #[test]
+    fn resize_finds_rewrapped_cursor() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abcdefghijkl"], (0, 14)));
+
+        // The first row now takes up two, and the cursor is at the start of
+        // the third.
+        let mut out = Vec::new();
+        renderer.resize(&mut out, 5).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[2A\x1b[1G\x1b[J");
+    }
+
+    #[test]
+    fn resize_within_row() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abcdefg", "error"], (0, 9)));
+
+        let mut out = Vec::new();
+        renderer.resize(&mut out, 4).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[2A\x1b[1G\x1b[J");
+
+        // Everything is drawn again, wrapped at the new width by the
+        // terminal itself, within each line.
+        assert_eq!(
+            render(&mut renderer, &frame(&["> abcdefg", "error"], (0, 9))),
+            "\x1b[0m> ab\x1b[0m\x1b[0mcdef\x1b[0m\x1b[0mg\x1b[0m\r\n\x1b[0merro\x1b[0m\x1b[0mr\x1b[0m\x1b[2A"
+        );
+    }
+
+    #[test]
+    fn resize_wider() {
+        let mut renderer = Renderer::new(10);
+        render(&mut renderer, &frame(&["> abcdefghijkl"], (0, 14)));
+
+        // The two rows are joined into one, with the cursor still on it.
+        let mut out = Vec::new();
+        renderer.resize(&mut out, 20).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[1G\x1b[J");
+    }
+
+    #[test]
+    fn resize_to_uneven_width() {
+        let mut renderer = Renderer::new(10);
+        render(
+            &mut renderer,
+            &frame(&["output", "> abcdefghijkl"], (1, 14)),
+        );
+
+        // The cursor is 14 cells into its line, on its fifth row.
+        let mut out = Vec::new();
+        renderer.resize(&mut out, 3).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[6A\x1b[1G\x1b[J");
+    }
+
+    #[test]
+    fn wraps_onto_continued_rows() {
+        let mut renderer = Renderer::new(5);
+        render(&mut renderer, &frame(&["> abc"], (0, 5)));
+
+        // The row the cursor moved onto was started with a newline; the
+        // last cell above is written again for the terminal to wrap.
+        assert_eq!(
+            render(&mut renderer, &frame(&["> abcd"], (0, 6))),
+            "\x1b[1A\x1b[5G\x1b[0mc\x1b[0m\x1b[0md\x1b[0m"
+        );
+
+        // So widening joins them, leaving the cursor on the first row.
+        let mut out = Vec::new();
+        renderer.resize(&mut out, 10).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[1G\x1b[J");
+    }
+}
@@ -0,0 +1,30 @@
+use crate::State;
+
+/// A line of text shown under the user input, such as key hints or a
+/// description of the selected candidate.
+///
+/// Returns the text to show, given the state of the editor. It may span
+/// several lines and contain escape sequences, as a prompt can. Nothing
+/// is shown for an empty string. The status is drawn under the message
+/// of any diagnostic and above the completion menu, and is asked for
+/// again whenever the editor is drawn.
+///
+/// Implemented for any function taking the state of the editor.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Editor, State};
+/// let editor = Editor::new(" > ").status(|state: &State| match state.selected {
+///     Some(c) => format!("Enter to insert {}", c.text),
+///     None => format!("{} chars", state.data.chars().count()),
+/// });
+/// ```
+pub trait Status {
+    fn status(&self, state: &State) -> String;
+}
+
+impl<F: Fn(&State) -> String> Status for F {
+    fn status(&self, state: &State) -> String {
+        self(state)
+    }
+}
@@ -0,0 +1,413 @@
+use std::env;
+use std::fmt::{self, Display};
+
+use crate::terminfo;
+
+/// The RGB values of the 16 named colors, as in xterm.
+const NAMED_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels of each channel in the 6x6x6 color cube of the 256-color
+/// palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+const NAMED: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+/// The colors a terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ColorSupport {
+    /// No colors at all.
+    None,
+    /// The 16 named colors.
+    Basic,
+    /// The 256-color palette.
+    Ansi256,
+    /// 24-bit colors.
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Detects the colors the terminal supports from the environment.
+    ///
+    /// `NO_COLOR` disables colors, and `COLORTERM` set to `truecolor` or
+    /// `24bit` enables all of them. Otherwise, `TERM` is checked for
+    /// common names, then looked up in the terminfo database, falling back
+    /// to the named colors.
+    pub fn detect() -> Self {
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return Self::None;
+        }
+
+        if let Ok(colorterm) = env::var("COLORTERM") {
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                return Self::TrueColor;
+            }
+        }
+
+        let Ok(term) = env::var("TERM") else {
+            return Self::Basic;
+        };
+
+        if term == "dumb" {
+            Self::None
+        } else if term.ends_with("-direct") {
+            Self::TrueColor
+        } else if term.ends_with("-256color") {
+            Self::Ansi256
+        } else {
+            match terminfo::max_colors(&term) {
+                Some(..=7) => Self::None,
+                Some(8..=255) | None => Self::Basic,
+                Some(256..=0xff_ffff) => Self::Ansi256,
+                Some(_) => Self::TrueColor,
+            }
+        }
+    }
+}
+
+impl Default for ColorSupport {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// A color from the 256-color palette.
+    Ansi(u8),
+    /// A 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Maps the color to the closest one the terminal supports, or `None`
+    /// if it supports no colors.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Color, ColorSupport};
+    /// let orange = Color::Rgb(255, 135, 0);
+    /// assert_eq!(orange.downsample(ColorSupport::Ansi256), Some(Color::Ansi(208)));
+    /// assert_eq!(orange.downsample(ColorSupport::Basic), Some(Color::Yellow));
+    /// assert_eq!(orange.downsample(ColorSupport::None), None);
+    /// ```
+    pub fn downsample(self, support: ColorSupport) -> Option<Self> {
+        match (self, support) {
+            (_, ColorSupport::None) => None,
+            (_, ColorSupport::TrueColor) => Some(self),
+            (Self::Rgb(r, g, b), ColorSupport::Ansi256) => Some(Self::Ansi(to_ansi256(r, g, b))),
+            (Self::Ansi(n), ColorSupport::Basic) => {
+                let (r, g, b) = ansi256_rgb(n);
+                Some(NAMED[nearest_named(r, g, b)])
+            }
+            (Self::Rgb(r, g, b), ColorSupport::Basic) => Some(NAMED[nearest_named(r, g, b)]),
+            _ => Some(self),
+        }
+    }
+
+    /// Writes the SGR parameters selecting the color, where `base` is 30
+    /// for the foreground, 40 for the background, and 50 for underlines.
+    fn write_sgr(self, f: &mut fmt::Formatter<'_>, base: u8) -> fmt::Result {
+        // The named colors are the first 16 of the 256-color palette.
+        let n = match self {
+            Self::Black => 0,
+            Self::Red => 1,
+            Self::Green => 2,
+            Self::Yellow => 3,
+            Self::Blue => 4,
+            Self::Magenta => 5,
+            Self::Cyan => 6,
+            Self::White => 7,
+            Self::BrightBlack => 8,
+            Self::BrightRed => 9,
+            Self::BrightGreen => 10,
+            Self::BrightYellow => 11,
+            Self::BrightBlue => 12,
+            Self::BrightMagenta => 13,
+            Self::BrightCyan => 14,
+            Self::BrightWhite => 15,
+            Self::Ansi(n) => return write!(f, "{};5;{n}", base + 8),
+            Self::Rgb(r, g, b) => return write!(f, "{};2;{r};{g};{b}", base + 8),
+        };
+
+        // Underline colors only have the 256-color and 24-bit forms.
+        if base == 50 {
+            write!(f, "58;5;{n}")
+        } else if n < 8 {
+            write!(f, "{}", base + n)
+        } else {
+            write!(f, "{}", base + 60 + n - 8)
+        }
+    }
+}
+
+/// The RGB value of a color of the 256-color palette.
+fn ansi256_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => NAMED_RGB[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            let level = |i: u8| CUBE_LEVELS[i as usize];
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The closest color of the 256-color palette, leaving out the named
+/// colors, whose values vary between terminals.
+fn to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap() as u8
+    };
+
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance((r, g, b), ansi256_rgb(gray)) < distance((r, g, b), ansi256_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The index of the closest named color.
+fn nearest_named(r: u8, g: u8, b: u8) -> usize {
+    (0..16)
+        .min_by_key(|&i| distance((r, g, b), NAMED_RGB[i]))
+        .unwrap()
+}
+
+/// The squared distance between two colors.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// How to draw a piece of text.
+///
+/// Displays as the escape sequence that switches to it, or nothing for the
+/// default style.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Color, Style};
+/// let style = Style::default().fg(Color::Red).bold(true);
+/// assert_eq!(style.to_string(), "\x1b[1;31m");
+/// assert_eq!(Style::default().to_string(), "");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Whether the underline is curly, where the terminal supports it.
+    pub undercurl: bool,
+    pub underline_color: Option<Color>,
+}
+
+impl Style {
+    /// Sets the foreground color.
+    pub fn fg(self, fg: Color) -> Self {
+        Self {
+            fg: Some(fg),
+            ..self
+        }
+    }
+
+    /// Sets the background color.
+    pub fn bg(self, bg: Color) -> Self {
+        Self {
+            bg: Some(bg),
+            ..self
+        }
+    }
+
+    /// Sets whether the text is bold.
+    pub fn bold(self, bold: bool) -> Self {
+        Self { bold, ..self }
+    }
+
+    /// Sets whether the text is dimmed.
+    pub fn dim(self, dim: bool) -> Self {
+        Self { dim, ..self }
+    }
+
+    /// Sets whether the text is italic.
+    pub fn italic(self, italic: bool) -> Self {
+        Self { italic, ..self }
+    }
+
+    /// Sets whether the text is underlined.
+    pub fn underline(self, underline: bool) -> Self {
+        Self { underline, ..self }
+    }
+
+    /// Sets whether the text has a curly underline, where the terminal
+    /// supports it.
+    pub fn undercurl(self, undercurl: bool) -> Self {
+        Self { undercurl, ..self }
+    }
+
+    /// Sets the color of the underline.
+    pub fn underline_color(self, underline_color: Color) -> Self {
+        Self {
+            underline_color: Some(underline_color),
+            ..self
+        }
+    }
+
+    /// Maps the colors of the style to those the terminal supports; see
+    /// [`Color::downsample`].
+    ///
+    /// Terminals without 256 colors are assumed not to support curly or
+    /// colored underlines, so those become plain underlines.
+    pub fn downsample(self, support: ColorSupport) -> Self {
+        let basic = support <= ColorSupport::Basic;
+        Self {
+            fg: self.fg.and_then(|c| c.downsample(support)),
+            bg: self.bg.and_then(|c| c.downsample(support)),
+            underline: self.underline || (basic && self.undercurl),
+            undercurl: self.undercurl && !basic,
+            underline_color: self
+                .underline_color
+                .filter(|_| !basic)
+                .and_then(|c| c.downsample(support)),
+            ..self
+        }
+    }
+
+    /// Draws `other` over this style: its colors replace these where set,
+    /// and its attributes are added to these.
+    ///
+    /// Example:
+    /// ```
+    /// # use linoleum::{Color, Style};
+    /// let base = Style::default().fg(Color::Blue).bold(true);
+    /// let over = Style::default().fg(Color::Red).underline(true);
+    /// assert_eq!(
+    ///     base.patch(over),
+    ///     Style::default().fg(Color::Red).bold(true).underline(true),
+    /// );
+    /// ```
+    pub fn patch(self, other: Style) -> Self {
+        Self {
+            fg: other.fg.or(self.fg),
+            bg: other.bg.or(self.bg),
+            bold: self.bold || other.bold,
+            dim: self.dim || other.dim,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+            undercurl: self.undercurl || other.undercurl,
+            underline_color: other.underline_color.or(self.underline_color),
+        }
+    }
+}
+
+impl Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Self::default() {
+            return Ok(());
+        }
+
+        write!(f, "\x1b[")?;
+
+        let mut first = true;
+        let mut sep = |f: &mut fmt::Formatter<'_>| {
+            if !std::mem::take(&mut first) {
+                write!(f, ";")?;
+            }
+            Ok(())
+        };
+
+        for (set, code) in [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline && !self.undercurl, "4"),
+            (self.undercurl, "4:3"),
+        ] {
+            if set {
+                sep(f)?;
+                write!(f, "{code}")?;
+            }
+        }
+
+        if let Some(fg) = self.fg {
+            sep(f)?;
+            fg.write_sgr(f, 30)?;
+        }
+
+        if let Some(bg) = self.bg {
+            sep(f)?;
+            bg.write_sgr(f, 40)?;
+        }
+
+        if let Some(color) = self.underline_color {
+            sep(f)?;
+            color.write_sgr(f, 50)?;
+        }
+
+        write!(f, "m")
+    }
+}
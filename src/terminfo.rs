@@ -0,0 +1,75 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The magic numbers of the legacy and extended terminfo formats, which
+/// store numbers in 16 and 32 bits respectively.
+const MAGIC_LEGACY: u16 = 0o432;
+const MAGIC_EXTENDED: u16 = 0o1036;
+
+/// The index of `max_colors` among the numeric capabilities.
+const MAX_COLORS: usize = 13;
+
+/// Looks up the number of colors `term` supports in its compiled terminfo
+/// entry, if it can be found. Terminals without colors have a negative
+/// number.
+pub(crate) fn max_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let entry = dirs().into_iter().find_map(|dir| {
+        fs::read(dir.join(first.to_string()).join(term))
+            .or_else(|_| fs::read(dir.join(format!("{:x}", first as u32)).join(term)))
+            .ok()
+    })?;
+
+    let read_u16 = |i: usize| Some(u16::from_le_bytes([*entry.get(i)?, *entry.get(i + 1)?]));
+
+    let width = match read_u16(0)? {
+        MAGIC_LEGACY => 2,
+        MAGIC_EXTENDED => 4,
+        _ => return None,
+    };
+
+    let names = read_u16(2)? as usize;
+    let bools = read_u16(4)? as usize;
+    let numbers = read_u16(6)? as usize;
+    if MAX_COLORS >= numbers {
+        return Some(-1);
+    }
+
+    // The numbers are aligned to an even offset.
+    let mut offset = 12 + names + bools;
+    offset += offset % 2;
+    offset += MAX_COLORS * width;
+
+    let bytes = entry.get(offset..offset + width)?;
+    Some(if width == 2 {
+        i16::from_le_bytes([bytes[0], bytes[1]]) as i32
+    } else {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    })
+}
+
+/// The directories to search for terminfo entries, in order.
+fn dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .into_iter()
+            .map(PathBuf::from),
+    );
+
+    dirs
+}
@@ -0,0 +1,149 @@
+use std::env;
+
+use crate::{Color, ColorSupport, Severity, Style};
+
+/// The styles of everything the editor draws besides the input itself.
+///
+/// The default theme is [`Theme::dark`], or [`Theme::monochrome`] if the
+/// `NO_COLOR` environment variable is set to anything but an empty string.
+/// On terminals without colors, styles that rely on them are replaced with
+/// those of [`Theme::monochrome`].
+///
+/// Hints and descriptions go on the status line, in its style; see
+/// [`Editor::status`](crate::Editor::status). The editor has no history
+/// search or multi-line input yet, so there are no styles for a search
+/// prompt or a continuation prompt.
+///
+/// Example:
+/// ```
+/// # use linoleum::{Color, Editor, Style, Theme};
+/// let theme = Theme {
+///     menu_selected: Style::default().fg(Color::Magenta).bold(true),
+///     ..Theme::light()
+/// };
+///
+/// let editor = Editor::new(" > ").theme(theme);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Theme {
+    /// The candidates in the completion menu, and its spinner.
+    pub menu: Style,
+    /// The selected candidate in the completion menu.
+    pub menu_selected: Style,
+    /// The characters of candidates that matched the input, drawn over
+    /// the style of the candidate.
+    pub menu_matched: Style,
+    /// The headers of groups of candidates.
+    pub menu_header: Style,
+    /// The selected candidate, previewed in the input.
+    pub preview: Style,
+    /// The messages of error diagnostics. Their ranges are underlined in
+    /// its foreground color.
+    pub error: Style,
+    /// The messages of warning diagnostics. Their ranges are underlined in
+    /// its foreground color.
+    pub warning: Style,
+    /// The messages of informational diagnostics. Their ranges are
+    /// underlined in its foreground color.
+    pub info: Style,
+    /// The status line, such as hints or the description of the selected
+    /// candidate. Escape sequences in its text are drawn over it.
+    pub status: Style,
+}
+
+impl Theme {
+    /// A theme for terminals with a dark background.
+    pub fn dark() -> Self {
+        Self {
+            menu: Style::default(),
+            menu_selected: Style::default().fg(Color::Ansi(6)),
+            menu_matched: Style::default().bold(true),
+            menu_header: Style::default().bold(true).underline(true),
+            preview: Style::default().dim(true),
+            error: Style::default().fg(Color::Red),
+            warning: Style::default().fg(Color::Yellow),
+            info: Style::default().fg(Color::Blue),
+            status: Style::default().dim(true),
+        }
+    }
+
+    /// A theme for terminals with a light background.
+    pub fn light() -> Self {
+        Self {
+            menu_selected: Style::default().fg(Color::Blue),
+            preview: Style::default().fg(Color::BrightBlack),
+            warning: Style::default().fg(Color::Magenta),
+            ..Self::dark()
+        }
+    }
+
+    /// A theme without any colors.
+    pub fn monochrome() -> Self {
+        Self {
+            menu: Style::default(),
+            menu_selected: Style::default().bold(true).underline(true),
+            menu_matched: Style::default().bold(true),
+            menu_header: Style::default().underline(true),
+            preview: Style::default().dim(true),
+            error: Style::default().bold(true),
+            warning: Style::default(),
+            info: Style::default().dim(true),
+            status: Style::default().dim(true),
+        }
+    }
+
+    /// Maps every style of the theme to the colors the terminal supports.
+    /// Without any colors, styles that relied on them fall back to those
+    /// of [`Theme::monochrome`], so they can still be told apart.
+    pub(crate) fn downsample(self, support: ColorSupport) -> Self {
+        let mono = Self::monochrome();
+        let pick = |style: Style, fallback: Style| {
+            let downsampled = style.downsample(support);
+            if support == ColorSupport::None && downsampled != style {
+                fallback
+            } else {
+                downsampled
+            }
+        };
+
+        Self {
+            menu: pick(self.menu, mono.menu),
+            menu_selected: pick(self.menu_selected, mono.menu_selected),
+            menu_matched: pick(self.menu_matched, mono.menu_matched),
+            menu_header: pick(self.menu_header, mono.menu_header),
+            preview: pick(self.preview, mono.preview),
+            error: pick(self.error, mono.error),
+            warning: pick(self.warning, mono.warning),
+            info: pick(self.info, mono.info),
+            status: pick(self.status, mono.status),
+        }
+    }
+
+    /// The style of the message of a diagnostic.
+    pub(crate) fn message(&self, severity: Severity) -> Style {
+        match severity {
+            Severity::Error => self.error,
+            Severity::Warning => self.warning,
+            Severity::Info => self.info,
+        }
+    }
+
+    /// The style of the range of a diagnostic.
+    pub(crate) fn diagnostic(&self, severity: Severity) -> Style {
+        let style = Style::default().undercurl(true);
+        match self.message(severity).fg {
+            Some(color) => style.underline_color(color),
+            None => style,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            Self::monochrome()
+        } else {
+            Self::dark()
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Shell-like splitting of the input into words, used for completion.
+//!
+//! Words are separated by delimiters, which lose their meaning inside
+//! single or double quotes, or when escaped with a backslash. Inside
+//! double quotes, only `"` and `\` can be escaped; inside single quotes,
+//! nothing can.
+
+/// A word of the input, as found by [`word_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    /// The char index the word starts at, including any opening quote.
+    pub start: usize,
+    /// The quote the word was opened with, if any.
+    pub quote: Option<char>,
+    /// The text of the word, with quotes and escapes removed.
+    pub text: String,
+}
+
+impl Word {
+    /// The char index the contents of the word start at, after any
+    /// opening quote.
+    pub fn content_start(&self) -> usize {
+        self.start + usize::from(self.quote.is_some())
+    }
+}
+
+/// Finds the word ending at the char index `cursor`.
+///
+/// Example:
+/// ```
+/// # use linoleum::words::word_at;
+/// let word = word_at(r#"cat "my fi"#, 10, " ");
+/// assert_eq!(word.start, 4);
+/// assert_eq!(word.quote, Some('"'));
+/// assert_eq!(word.text, "my fi");
+///
+/// let word = word_at(r"cat a\ b", 8, " ");
+/// assert_eq!(word.start, 4);
+/// assert_eq!(word.text, "a b");
+/// ```
+pub fn word_at(data: &str, cursor: usize, delimiters: &str) -> Word {
+    let mut start = 0;
+    let mut text = String::new();
+
+    lex(
+        data.chars().take(cursor),
+        delimiters,
+        |i, lexed| match lexed {
+            Lexed::Char(ch) => text.push(ch),
+            Lexed::Quote => {}
+            Lexed::Delimiter => {
+                start = i + 1;
+                text.clear();
+            }
+        },
+    );
+
+    let quote = data
+        .chars()
+        .nth(start)
+        .filter(|&ch| start < cursor && is_quote(ch));
+
+    Word { start, quote, text }
+}
+
+/// Finds the text of the word a [`Completion`](crate::Completion) is asked
+/// to replace, given its `start` and `end`, with quotes and escapes
+/// removed. Unlike [`word_at`], this doesn't depend on the delimiters.
+///
+/// Example:
+/// ```
+/// # use linoleum::words::word_in;
+/// assert_eq!(word_in(r#"cat "my fi"#, 5, 10), "my fi");
+/// assert_eq!(word_in(r"--file=a\ b", 7, 11), "a b");
+/// ```
+pub fn word_in(data: &str, start: usize, end: usize) -> String {
+    // Words only start after a delimiter, so a quote right before the
+    // start must have opened the word.
+    let opened = start
+        .checked_sub(1)
+        .and_then(|i| data.chars().nth(i))
+        .is_some_and(is_quote);
+
+    let mut text = String::new();
+    lex(
+        data.chars().take(end).skip(start - usize::from(opened)),
+        "",
+        |_, lexed| {
+            if let Lexed::Char(ch) = lexed {
+                text.push(ch);
+            }
+        },
+    );
+
+    text
+}
+
+/// Splits the input into words, removing quotes and escapes.
+///
+/// Example:
+/// ```
+/// # use linoleum::words::split;
+/// assert_eq!(split(r#"open "my file" a\ b"#, " "), ["open", "my file", "a b"]);
+/// ```
+pub fn split(data: &str, delimiters: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+
+    lex(data.chars(), delimiters, |_, lexed| match lexed {
+        Lexed::Char(ch) => {
+            current.push(ch);
+            in_word = true;
+        }
+        Lexed::Quote => in_word = true,
+        Lexed::Delimiter => {
+            if in_word {
+                words.push(std::mem::take(&mut current));
+                in_word = false;
+            }
+        }
+    });
+
+    if in_word {
+        words.push(current);
+    }
+
+    words
+}
+
+/// Quotes and escapes text so that it lexes back into a single word,
+/// consistently with how the word was opened.
+///
+/// Quoted words are closed again, unless the text ends in a `/`, so that
+/// completing a directory can continue. Unquoted words escape delimiters,
+/// quotes, and backslashes with a backslash.
+///
+/// Example:
+/// ```
+/// # use linoleum::words::quote;
+/// assert_eq!(quote("my file", None, " "), r"my\ file");
+/// assert_eq!(quote("my \"file\"", Some('"'), " "), r#""my \"file\"""#);
+/// assert_eq!(quote("it's", Some('\''), " "), r"'it'\''s'");
+/// assert_eq!(quote("my dir/", Some('"'), " "), r#""my dir/"#);
+/// ```
+pub fn quote(text: &str, quote: Option<char>, delimiters: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+
+    match quote {
+        Some('\'') => {
+            quoted.push('\'');
+            quoted.push_str(&text.replace('\'', r"'\''"));
+        }
+        Some(q) => {
+            quoted.push(q);
+            for ch in text.chars() {
+                if ch == q || ch == '\\' {
+                    quoted.push('\\');
+                }
+                quoted.push(ch);
+            }
+        }
+        None => {
+            for ch in text.chars() {
+                if delimiters.contains(ch) || is_quote(ch) || ch == '\\' {
+                    quoted.push('\\');
+                }
+                quoted.push(ch);
+            }
+        }
+    }
+
+    if let Some(q) = quote {
+        if !text.ends_with('/') {
+            quoted.push(q);
+        }
+    }
+
+    quoted
+}
+
+fn is_quote(ch: char) -> bool {
+    ch == '"' || ch == '\''
+}
+
+/// A single step of lexing.
+enum Lexed {
+    /// A character belonging to a word, with escapes removed.
+    Char(char),
+    /// A quote, which makes a word even if it's empty.
+    Quote,
+    /// A delimiter between words.
+    Delimiter,
+}
+
+/// Runs through the input, calling `f` with the index of each character
+/// and what it lexed as. Backslashes that escape the next character are
+/// skipped.
+fn lex(data: impl Iterator<Item = char>, delimiters: &str, mut f: impl FnMut(usize, Lexed)) {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (i, ch) in data.enumerate() {
+        if escaped {
+            escaped = false;
+
+            // Inside double quotes, other backslashes are kept as-is.
+            if quote == Some('"') && ch != '"' && ch != '\\' {
+                f(i - 1, Lexed::Char('\\'));
+            }
+
+            f(i, Lexed::Char(ch));
+            continue;
+        }
+
+        match quote {
+            Some(q) if ch == q => {
+                quote = None;
+                f(i, Lexed::Quote);
+            }
+            Some('"') if ch == '\\' => escaped = true,
+            Some(_) => f(i, Lexed::Char(ch)),
+            None if ch == '\\' => escaped = true,
+            None if is_quote(ch) => {
+                quote = Some(ch);
+                f(i, Lexed::Quote);
+            }
+            None if delimiters.contains(ch) => f(i, Lexed::Delimiter),
+            None => f(i, Lexed::Char(ch)),
+        }
+    }
+}